@@ -0,0 +1,93 @@
+use crate::args::Args;
+
+use regex::Regex;
+use serde::Deserialize;
+use std::fs;
+
+/// Answers that are allowed to be given ahead of time; the rest only make sense interactively.
+pub const PRESET_ANSWERS: [&str; 5] = ["p", "N", "b", "B", "s"];
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AnswerRuleJson {
+    image: Option<String>,
+    image_regex: Option<String>,
+    answer: String,
+}
+
+enum ImageMatch {
+    Exact(String),
+    Regex(Regex),
+}
+
+struct AnswerRule {
+    image: ImageMatch,
+    answer: String,
+}
+
+/// Pre-supplied answers to the rebuild prompt, from `--answer-file` and `--assume`.
+pub struct Answers {
+    rules: Vec<AnswerRule>,
+    assume: Option<String>,
+}
+
+impl Answers {
+    pub fn load(args: &Args) -> Result<Answers, String> {
+        let mut rules = vec![];
+        if let Some(answer_file) = &args.answer_file {
+            let content = fs::read_to_string(answer_file)
+                .map_err(|e| format!("Failed to read '{}': {}", answer_file.display(), e))?;
+            let json: Vec<AnswerRuleJson> = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", answer_file.display(), e))?;
+            for rule in json {
+                if !PRESET_ANSWERS.contains(&rule.answer.as_str()) {
+                    return Err(format!(
+                        "Invalid answer '{}' in '{}', expected one of {}",
+                        rule.answer,
+                        answer_file.display(),
+                        PRESET_ANSWERS.join("/")
+                    ));
+                }
+                let image = match (rule.image, rule.image_regex) {
+                    (Some(image), None) => ImageMatch::Exact(image),
+                    (None, Some(pattern)) => ImageMatch::Regex(
+                        Regex::new(&pattern)
+                            .map_err(|e| format!("Invalid image_regex '{}': {}", pattern, e))?,
+                    ),
+                    _ => {
+                        return Err(format!(
+                            "Each entry in '{}' needs exactly one of image or image_regex",
+                            answer_file.display()
+                        ))
+                    }
+                };
+                rules.push(AnswerRule {
+                    image,
+                    answer: rule.answer,
+                });
+            }
+        }
+        Ok(Answers {
+            rules,
+            assume: args.assume.clone(),
+        })
+    }
+
+    /// The answer for `image` and where it came from. Exact matches win over regexes, which win over --assume.
+    pub fn answer_for(&self, image: &str) -> Option<(String, &'static str)> {
+        let exact = self.rules.iter().find(|r| match &r.image {
+            ImageMatch::Exact(name) => name == image,
+            ImageMatch::Regex(_) => false,
+        });
+        let regex = || {
+            self.rules.iter().find(|r| match &r.image {
+                ImageMatch::Exact(_) => false,
+                ImageMatch::Regex(re) => re.is_match(image),
+            })
+        };
+        if let Some(rule) = exact.or_else(regex) {
+            return Some((rule.answer.clone(), "--answer-file"));
+        }
+        self.assume.clone().map(|answer| (answer, "--assume"))
+    }
+}
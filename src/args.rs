@@ -1,12 +1,50 @@
-use clap::{Parser, ValueEnum};
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::log_sink_helper_fns as log_sink;
+use crate::project::Project;
+use crate::retag;
+
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use serde::Deserialize;
 use serde_json::Value;
 use std::fs::{self, File, OpenOptions};
-use std::io::{Read, Write};
+use std::io::{IsTerminal, Read, Write};
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::time::Duration;
 // use clap::builder::ValueParser;
 
 pub fn args_checks() -> Args {
-    let xx = Args::parse();
+    let mut xx = Args::parse();
+    color::init(xx.color);
+    // completions don't need any of the other args, so generate them before anything gets validated
+    if let Some(shell) = xx.generate_completions {
+        let mut cmd = Args::command();
+        let bin_name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, bin_name, &mut std::io::stdout());
+        std::process::exit(0);
+    }
+    if let Some(Command::Rebuild { filters }) = &xx.command {
+        xx.filters = filters.clone();
+    }
+    xx.mode = match (&xx.command, xx.mode_flag) {
+        (Some(_), Some(_)) => Args::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "give either a subcommand or --mode, not both",
+            )
+            .exit(),
+        (Some(command), None) => command.mode(),
+        (None, Some(mode)) => {
+            color::warn(format!(
+                "--mode is deprecated and goes away in the next release, use `{}` instead.",
+                Command::from_mode(mode).name()
+            ));
+            mode
+        }
+        (None, None) => Mode::Rebuild,
+    };
     xx
 }
 
@@ -19,98 +57,572 @@ pub struct Args {
         long,
         value_name = "PATH",
         default_value = ".",
-        value_parser = check_readable_dir
-    )]
+        value_parser = check_readable_dir, global = true)]
     pub path: PathBuf,
-    /// rebuild = pull latest docker.io images and rebuild custom images, secrets = refresh secrets files (not impl yet)
-    #[arg(short = 'm', long, default_value = "Rebuild", value_parser = clap::value_parser!(Mode))]
+    /// Deprecated, use the matching subcommand instead; kept for one release
+    #[arg(short = 'm', long = "mode", value_name = "MODE", value_parser = clap::value_parser!(Mode))]
+    pub mode_flag: Option<Mode>,
+    /// The subcommand, or --mode, resolved by args_checks
+    #[arg(skip = Mode::Rebuild)]
     pub mode: Mode,
+    /// rebuild's positional image or container name globs, copied out by args_checks
+    #[arg(skip)]
+    pub filters: Vec<String>,
     /// Optional path for storing intermediate secrets file, must be rw if supplied, and ideally secured to this user's group
-    #[arg(short = 's', long, value_name = "SECRETS_FILE", value_parser = check_readable_dir)]
+    #[arg(short = 's', long, value_name = "SECRETS_FILE", value_parser = check_readable_dir, global = true)]
     pub secrets_tmp_dir: Option<PathBuf>,
     /// Print extra stuff
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub verbose: bool,
     /// Regex pattern(s) to exclude paths, e.g., docker/archive or [^\.]+/archive
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub exclude_path_patterns: Vec<String>,
     /// Regex pattern(s) to include paths. If both incl. and excl. are specified, excl. is applied first.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub include_path_patterns: Vec<String>,
-    #[arg(short, long)]
+    /// Also scan dirs and files that .gitignore excludes, .git dirs, and dirs excluded at the rebuild prompt
+    #[arg(long, global = true)]
+    pub no_ignore: bool,
+    #[arg(short, long, global = true)]
     pub build_args: Vec<String>,
+    /// Build every image without podman's layer cache
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+    /// Don't label images built inside a git repo with the commit (org.opencontainers.image.revision) and remote (org.opencontainers.image.source)
+    #[arg(long, global = true)]
+    pub no_provenance_labels: bool,
+    /// Build arg set to the current unix time on every build, so steps after `ARG <NAME>` in a Dockerfile always rerun
+    #[arg(long, value_name = "NAME", global = true)]
+    pub build_arg_cache_bust: Option<String>,
+    /// Registry ref to use as build layer cache (--cache-from/--cache-to), `{name}` and `{tag}` are filled in per image
+    #[arg(long, value_name = "REF_TEMPLATE", global = true)]
+    pub build_cache: Option<String>,
+    /// Stop a build that runs longer than this, e.g. 45m, and count it as failed
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub build_timeout: Option<Duration>,
+    /// Also offer dirs with a Dockerfile/Containerfile but no compose or .container file, building them as this name, e.g. localhost/{dir}:{branch}; placeholders {dir}, {suffix}, {branch}, {date}
+    #[arg(long, value_name = "TEMPLATE", global = true)]
+    pub image_name_template: Option<String>,
+    /// Binary to pull and build images with; by default the first of podman, buildah and docker on PATH
+    #[arg(long, value_parser = clap::value_parser!(BuildEngine), global = true)]
+    pub build_engine: Option<BuildEngine>,
+    /// Build for each of these platforms, e.g. linux/amd64,linux/arm64, and make the image a manifest list of them
+    #[arg(long, value_name = "PLATFORMS", value_delimiter = ',', global = true)]
+    pub platforms: Vec<String>,
+    /// With --platforms, push the manifest list and its images once every platform has built
+    #[arg(long, requires = "platforms", global = true)]
+    pub push_manifest: bool,
+    /// Which podman storage to work in: user (rootless, the default) or system (rootful, through sudo)
+    #[arg(long, default_value = "user", value_parser = clap::value_parser!(PodmanScope), global = true)]
+    pub podman_scope: PodmanScope,
+    /// After a successful build, prune dangling images and report the space reclaimed
+    #[arg(long, global = true)]
+    pub prune_after_build: bool,
+    /// After a successful pull or build, scan the image with trivy and summarize the findings per severity
+    #[arg(long, global = true)]
+    pub scan_after_build: bool,
+    /// When to style output: auto (on a terminal, unless NO_COLOR is set), always or never
+    #[arg(long, value_name = "WHEN", default_value = "auto", value_parser = clap::value_parser!(ColorWhen), global = true)]
+    pub color: ColorWhen,
+    /// Ring the terminal bell when a prompt comes up after this many seconds without any input
+    #[arg(long, value_name = "SECONDS", global = true)]
+    pub bell_after: Option<u64>,
+    /// Stop looking for compose files after this much time walking dirs, e.g. 30s, and go on with what was found
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub scan_timeout: Option<Duration>,
+    /// Only offer images pulled or built longer ago than this, e.g. 30d, 12h, 2w
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub since: Option<Duration>,
+    /// Dir for exported images and reports, each in a subdir per kind (exports/, diagnostics/); default is the current dir
+    #[arg(long, value_name = "DIR", global = true)]
+    pub output_dir: Option<PathBuf>,
+    /// In list mode, what to print
+    #[arg(long, default_value = "table", value_parser = clap::value_parser!(ListFormat), global = true)]
+    pub format: ListFormat,
+    /// How dates in the image details are shown: relative (3 days ago), absolute (ISO 8601) or both
+    #[arg(long, value_parser = clap::value_parser!(TimeFormat), global = true)]
+    pub time_format: Option<TimeFormat>,
+    /// In diagnostics mode, where to write the bundle (default pcm-diagnostics-<timestamp>.tar.gz)
+    #[arg(long, value_name = "TARBALL", value_parser = check_parent_dir_is_writeable, global = true)]
+    pub diagnostics_output: Option<PathBuf>,
+    /// In drift mode, the `list --format json` output to compare against
+    #[arg(long, value_name = "FILE", value_parser = check_readable_file, global = true)]
+    pub baseline: Option<PathBuf>,
+    /// In compose-to-quadlet, the dir to write the quadlet units to, e.g. ~/.config/containers/systemd
+    #[arg(long, value_name = "DIR", global = true)]
+    pub quadlet_dir: Option<PathBuf>,
+    /// In retag, only list the local images matching this glob, e.g. 'localhost/foo-*'
+    #[arg(long, value_name = "GLOB", global = true)]
+    pub images: Option<String>,
+    /// In retag, the edit to apply to each selected name: old=new, or s/regex/replacement/
+    #[arg(long, value_name = "EDIT", global = true)]
+    pub rename: Option<String>,
+    /// In retag, add the new names without removing the old ones
+    #[arg(long, global = true)]
+    pub keep_old_names: bool,
+    /// In repo-check, also lint each Dockerfile/Containerfile, with hadolint when it's installed
+    #[arg(long, global = true)]
+    pub lint: bool,
+    /// In lint, repo-check, drift and usage-report modes, print the results as a JSON array
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// In lint mode, set an image rule's level: implicit-tag, floating-tag or registry-typo =off/info/warning/error
+    #[arg(long, value_name = "RULE=LEVEL", global = true)]
+    pub lint_rule: Vec<String>,
+    /// In lint mode, paths matching any of these count as production, where floating tags are flagged
+    #[arg(long, global = true)]
+    pub production_path_patterns: Vec<String>,
+    /// Mask the values of the secrets in --secret-mode-input-json wherever they show up in command output or the audit log
+    #[arg(long, global = true)]
+    pub redact_secrets: bool,
+    /// Mirror every line of podman output to journald, syslog or file:<path> as it happens
+    #[arg(long, value_name = "SINK", value_parser = log_sink::check_log_sink, global = true)]
+    pub log_sink: Option<String>,
+    /// Stop at the first failed pull or build
+    #[arg(long, conflicts_with = "max_failures", global = true)]
+    pub fail_fast: bool,
+    /// Stop once this many pulls or builds have failed
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), global = true)]
+    pub max_failures: Option<u32>,
+    /// After a successful pull or build, recreate the services using the image and restart their dependents in depends_on order
+    #[arg(long, global = true)]
+    pub restart_after_build: bool,
+    /// With --restart-after-build, how long to wait for a recreated service with a healthcheck to turn healthy
+    #[arg(long, value_name = "DURATION", default_value = "2m", value_parser = parse_duration, global = true)]
+    pub health_timeout: Duration,
     /// Pass as guid or filepath
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub secrets_client_id: Option<String>,
     /// Pass as filepath
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub secrets_client_secret_path: Option<PathBuf>,
     /// Pass as guid or filepath
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub secrets_tenant_id: Option<String>,
     /// Pass as guid or filepath
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub secrets_vault_name: Option<String>,
-    #[arg(long, value_parser = check_parent_dir_is_writeable)]
+    #[arg(long, value_parser = check_parent_dir_is_writeable, global = true)]
     pub secret_mode_output_json: Option<PathBuf>,
-    #[arg(long, value_parser = check_readable_file)]
+    #[arg(long, value_parser = check_readable_file, global = true)]
     pub secret_mode_input_json: Option<PathBuf>,
+    /// With secrets upload, upload the files under --path matching this glob (relative to --path, e.g. **/*.env or certs/*.pem) instead of just .env files; can be passed more than once
+    #[arg(long, value_name = "GLOB", global = true)]
+    pub secret_include: Vec<String>,
+    /// With secrets gc, only count secrets whose name starts with this as orphans, and offer to delete them. Names are the uploaded file's path with each character other than a letter, digit or `-` turned into `-`, e.g. home-me-stacks-
+    #[arg(long, value_name = "PREFIX", global = true)]
+    pub secret_gc_prefix: Option<String>,
+    /// With --secret-include, upload without listing the files and asking first. In compose-to-quadlet and retag, write the units or rename the images without asking
+    #[arg(long, global = true)]
+    pub yes: bool,
+    /// Encrypt secrets with this key before uploading them, and decrypt them when retrieving. The file holds 32 base64 encoded bytes.
+    #[arg(long, value_parser = check_readable_file, global = true)]
+    pub encryption_key_file: Option<PathBuf>,
+    /// Sign each secret with this Ed25519 key when uploading, recording the signature in the output json. The file holds a 32 byte seed, base64 encoded.
+    #[arg(long, value_parser = check_readable_file, global = true)]
+    pub signing_key_file: Option<PathBuf>,
+    /// Check each secret's signature against this Ed25519 public key when retrieving, failing unsigned or altered ones. The file holds 32 base64 encoded bytes.
+    #[arg(long, value_parser = check_readable_file, global = true)]
+    pub verify_key_file: Option<PathBuf>,
+    /// With secrets retrieve, how many secrets to download from the vault at once. Prompts still happen one at a time.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u16).range(1..), global = true)]
+    pub download_concurrency: u16,
+    /// Keep memory use down on small hosts: no index of which files use each image, and no secrets prefetching
+    #[arg(long, conflicts_with = "download_concurrency", global = true)]
+    pub low_memory: bool,
+    /// Project file (pcm-project.toml) naming scan roots, path patterns, registry and secrets settings
+    #[arg(long, value_name = "PROJECT_FILE", value_parser = check_readable_file, global = true)]
+    pub project: Option<PathBuf>,
+    /// Apply the project file's [profile.<name>] table over its defaults
+    #[arg(long, value_name = "NAME", requires = "project", global = true)]
+    pub profile: Option<String>,
+    #[arg(skip)]
+    pub loaded_project: Option<Project>,
+    /// In dependency-graph mode, also write the graph in Graphviz DOT format to this file
+    #[arg(long, value_name = "DOT_FILE", value_parser = check_parent_dir_is_writeable, global = true)]
+    pub dot_output: Option<PathBuf>,
+    /// Print a shell completion script to stdout and exit, e.g. --generate-completions bash > /etc/bash_completion.d/podman-compose-mgr
+    #[arg(long, value_name = "SHELL", global = true)]
+    pub generate_completions: Option<Shell>,
+    /// Answer the rebuild prompt with this for every image not matched by --answer-file, so it can run unattended
+    #[arg(long, value_parser = crate::answers::PRESET_ANSWERS, global = true)]
+    pub assume: Option<String>,
+    /// JSON list of rebuild prompt answers per image, e.g. [{"image": "docker.io/library/nginx:latest", "answer": "p"}, {"image_regex": "^localhost/", "answer": "b"}]
+    #[arg(long, value_name = "ANSWER_FILE", value_parser = check_valid_json_file, global = true)]
+    pub answer_file: Option<PathBuf>,
+    /// In rebuild mode, keep running and scan again this long after each scan ends, e.g. 6h. Needs --assume or --answer-file, as there's no one at the prompt
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, global = true)]
+    pub daemon: Option<Duration>,
+    /// With --daemon, serve Prometheus metrics at http://<ADDR>/metrics, e.g. 127.0.0.1:9464
+    #[arg(long, value_name = "ADDR", requires = "daemon", global = true)]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Don't check the registries the images come from can be reached before a rebuild starts
+    #[arg(long, global = true)]
+    pub skip_preflight: bool,
+    /// Before scanning, `podman load` every image tarball (.tar, .tar.gz, .tgz, .tar.xz) in this dir
+    #[arg(long, value_name = "DIR", value_parser = check_readable_dir, global = true)]
+    pub import_images: Option<PathBuf>,
+    /// Append a JSON line to this file for every build, pull, prune, restart and secret upload
+    #[arg(long, value_name = "AUDIT_LOG", value_parser = check_parent_dir_is_writeable, global = true)]
+    pub audit_log: Option<PathBuf>,
+    /// Dir for persisted state, defaults to $XDG_STATE_HOME/podman-compose-mgr
+    #[arg(long, value_name = "STATE_DIR", global = true)]
+    pub state_dir: Option<PathBuf>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
 impl Args {
-    /// Validate the secrets based on the mode
-    pub fn validate(&self) -> Result<(), String> {
-        if let Mode::SecretRefresh = self.mode {
-            if let Some(client_id) = &self.secrets_client_id {
-                if client_id.len() != 8 {
-                    return Err(
-                        "secrets_client_id must be exactly 8 characters long when Mode is Secrets."
-                            .to_string(),
-                    );
-                }
-            }
+    /// Dirs to walk: the project's roots if a project is loaded and names any, else --path
+    pub fn scan_roots(&self) -> Vec<PathBuf> {
+        match &self.loaded_project {
+            Some(project) if !project.roots.is_empty() => project.roots.clone(),
+            _ => vec![self.path.clone()],
+        }
+    }
 
-            if let Some(client_secret) = &self.secrets_client_secret_path {
-                if let Err(e) = check_readable_file(client_secret.to_str().unwrap()) {
-                    return Err(e);
-                }
+    /// Checks clap can't express, for the subcommand being run and then for any of them
+    pub fn validate(&self) -> Result<(), String> {
+        match self.mode {
+            Mode::SecretRefresh => self.validate_secrets_upload()?,
+            Mode::SecretRetrieve => self.validate_secrets_retrieve()?,
+            Mode::SecretGc => self.validate_secrets_gc()?,
+            Mode::Drift => self.validate_drift()?,
+            Mode::ComposeToQuadlet => self.validate_compose_to_quadlet()?,
+            Mode::Retag => self.validate_retag()?,
+            _ => {}
+        }
+        if self.daemon.is_some() {
+            if !matches!(self.mode, Mode::Rebuild) {
+                return Err("--daemon only works with rebuild.".to_string());
             }
-        } else if let Mode::SecretRetrieve = self.mode {
-            if let Some(client_id) = &self.secrets_client_id {
-                if client_id.len() != 36 {
-                    return Err("Azure client_id must be 36 characters long.".to_string());
-                }
+            if self.assume.is_none() && self.answer_file.is_none() {
+                return Err(
+                    "--daemon needs --assume or --answer-file to answer the rebuild prompt."
+                        .to_string(),
+                );
             }
+        }
+        if !self.platforms.is_empty() && self.build_engine == Some(BuildEngine::Docker) {
+            return Err("--platforms needs podman or buildah, not docker.".to_string());
+        }
+        if let Some(platform) = self
+            .platforms
+            .iter()
+            .find(|p| p.split('/').count() < 2 || p.split('/').any(|part| part.is_empty()))
+        {
+            return Err(format!(
+                "'{}' isn't a platform, give os/arch such as linux/arm64.",
+                platform
+            ));
+        }
+        if self.redact_secrets && self.secret_mode_input_json.is_none() {
+            return Err(
+                "--redact-secrets needs --secret-mode-input-json to know the secrets.".to_string(),
+            );
+        }
+        Ok(())
+    }
 
-            if let Some(client_secret) = &self.secrets_client_secret_path {
-                if let Err(e) = check_readable_file(client_secret.to_str().unwrap()) {
-                    return Err(e);
-                }
+    fn validate_secrets_upload(&self) -> Result<(), String> {
+        if let Some(client_id) = &self.secrets_client_id {
+            if client_id.len() != 8 {
+                return Err(
+                    "secrets_client_id must be exactly 8 characters long for secrets upload."
+                        .to_string(),
+                );
             }
+        }
+        if let Some(client_secret) = &self.secrets_client_secret_path {
+            check_readable_file(client_secret.to_str().unwrap())?;
+        }
+        Ok(())
+    }
 
-            if let Some(output_json) = &self.secret_mode_output_json {
-                if let Err(e) = check_parent_dir_is_writeable(output_json.to_str().unwrap()) {
-                    return Err(e);
-                }
+    fn validate_secrets_retrieve(&self) -> Result<(), String> {
+        if let Some(client_id) = &self.secrets_client_id {
+            if client_id.len() != 36 {
+                return Err("Azure client_id must be 36 characters long.".to_string());
             }
+        }
+        if let Some(client_secret) = &self.secrets_client_secret_path {
+            check_readable_file(client_secret.to_str().unwrap())?;
+        }
+        if let Some(output_json) = &self.secret_mode_output_json {
+            check_parent_dir_is_writeable(output_json.to_str().unwrap())?;
+        }
+        if let Some(input_json) = &self.secret_mode_input_json {
+            check_valid_json_file(input_json.to_str().unwrap())?;
+        }
+        Ok(())
+    }
 
-            if let Some(input_json) = &self.secret_mode_input_json {
-                if let Err(e) = check_valid_json_file(input_json.to_str().unwrap()) {
-                    return Err(e);
-                }
-            }
+    fn validate_secrets_gc(&self) -> Result<(), String> {
+        if self.secret_mode_input_json.is_none() {
+            return Err(
+                "secrets gc needs --secret-mode-input-json to know which secrets are in use."
+                    .to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_compose_to_quadlet(&self) -> Result<(), String> {
+        if self.quadlet_dir.is_none() {
+            return Err(
+                "compose-to-quadlet needs --quadlet-dir to write the units to.".to_string(),
+            );
+        }
+        Ok(())
+    }
+
+    fn validate_retag(&self) -> Result<(), String> {
+        if let Some(spec) = &self.rename {
+            retag::Edit::parse(spec)?;
+        } else if !std::io::stdin().is_terminal() {
+            return Err("retag needs --rename when not run from a terminal.".to_string());
+        }
+        Ok(())
+    }
+
+    fn validate_drift(&self) -> Result<(), String> {
+        if self.baseline.is_none() {
+            return Err("drift needs --baseline, a saved `list --format json`.".to_string());
         }
         Ok(())
     }
 }
 
-/// Enumeration of possible modes
+/// What to do, picked by a subcommand. Also what the deprecated --mode takes.
 #[derive(Clone, ValueEnum, Debug, Copy)]
 pub enum Mode {
     Rebuild,
     SecretRefresh,
     SecretRetrieve,
+    SecretCheckAuth,
+    SecretGc,
     RestartSvcs,
+    StateClean,
+    DependencyGraph,
+    Lint,
+    RepoCheck,
+    Doctor,
+    Diagnostics,
+    List,
+    Drift,
+    ComposeToQuadlet,
+    UsageReport,
+    Retag,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum Command {
+    /// Pull the latest upstream images and rebuild custom ones (the default)
+    Rebuild {
+        /// Only offer images or containers whose name matches one of these globs, e.g. 'app-*'
+        #[arg(value_name = "NAME")]
+        filters: Vec<String>,
+    },
+    /// List the services, their images and containers
+    #[command(alias = "scan")]
+    List,
+    /// Compare the services found against a saved `list --format json`
+    Drift,
+    /// Check compose files for problems
+    Lint,
+    /// Check build files and compose files agree with each other
+    RepoCheck,
+    /// Check podman, the state dir and the rest of the setup
+    Doctor,
+    /// Bundle up logs, versions and history for a bug report
+    Diagnostics,
+    /// Show depends_on between services
+    DependencyGraph,
+    /// Restart the services of each compose file
+    RestartSvcs,
+    /// Wipe the state dir
+    StateClean,
+    /// Write quadlet .container, .volume and .network units for the compose services
+    ComposeToQuadlet,
+    /// List each image with the compose and .container files using it, by tag
+    UsageReport,
+    /// Rename several local images at once, with one find/replace over their names
+    Retag,
+    /// Manage secrets in Azure Key Vault
+    Secrets {
+        #[command(subcommand)]
+        action: SecretsCommand,
+    },
+}
+
+#[derive(Subcommand, Clone, Copy, Debug)]
+pub enum SecretsCommand {
+    /// Upload secrets files under --path
+    #[command(alias = "refresh")]
+    Upload,
+    /// Download the secrets in --secret-mode-input-json
+    Retrieve,
+    /// Check the Key Vault credentials work
+    CheckAuth,
+    /// Delete secrets no longer in --secret-mode-input-json
+    Gc,
+}
+
+impl Command {
+    pub fn mode(&self) -> Mode {
+        match self {
+            Command::Rebuild { .. } => Mode::Rebuild,
+            Command::List => Mode::List,
+            Command::Drift => Mode::Drift,
+            Command::Lint => Mode::Lint,
+            Command::RepoCheck => Mode::RepoCheck,
+            Command::Doctor => Mode::Doctor,
+            Command::Diagnostics => Mode::Diagnostics,
+            Command::DependencyGraph => Mode::DependencyGraph,
+            Command::RestartSvcs => Mode::RestartSvcs,
+            Command::StateClean => Mode::StateClean,
+            Command::ComposeToQuadlet => Mode::ComposeToQuadlet,
+            Command::UsageReport => Mode::UsageReport,
+            Command::Retag => Mode::Retag,
+            Command::Secrets { action } => match *action {
+                SecretsCommand::Upload => Mode::SecretRefresh,
+                SecretsCommand::Retrieve => Mode::SecretRetrieve,
+                SecretsCommand::CheckAuth => Mode::SecretCheckAuth,
+                SecretsCommand::Gc => Mode::SecretGc,
+            },
+        }
+    }
+
+    pub fn from_mode(mode: Mode) -> Command {
+        let secrets = |action| Command::Secrets { action };
+        match mode {
+            Mode::Rebuild => Command::Rebuild { filters: vec![] },
+            Mode::List => Command::List,
+            Mode::Drift => Command::Drift,
+            Mode::Lint => Command::Lint,
+            Mode::RepoCheck => Command::RepoCheck,
+            Mode::Doctor => Command::Doctor,
+            Mode::Diagnostics => Command::Diagnostics,
+            Mode::DependencyGraph => Command::DependencyGraph,
+            Mode::RestartSvcs => Command::RestartSvcs,
+            Mode::StateClean => Command::StateClean,
+            Mode::ComposeToQuadlet => Command::ComposeToQuadlet,
+            Mode::UsageReport => Command::UsageReport,
+            Mode::Retag => Command::Retag,
+            Mode::SecretRefresh => secrets(SecretsCommand::Upload),
+            Mode::SecretRetrieve => secrets(SecretsCommand::Retrieve),
+            Mode::SecretCheckAuth => secrets(SecretsCommand::CheckAuth),
+            Mode::SecretGc => secrets(SecretsCommand::Gc),
+        }
+    }
+
+    /// How it's typed on the command line, e.g. `secrets upload`.
+    pub fn name(&self) -> String {
+        match self {
+            Command::Secrets { action } => format!(
+                "secrets {}",
+                match action {
+                    SecretsCommand::Upload => "upload",
+                    SecretsCommand::Retrieve => "retrieve",
+                    SecretsCommand::CheckAuth => "check-auth",
+                    SecretsCommand::Gc => "gc",
+                }
+            ),
+            command => command
+                .mode()
+                .to_possible_value()
+                .map(|v| v.get_name().to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Output of list mode
+#[derive(Clone, ValueEnum, Debug, Copy, PartialEq)]
+pub enum ListFormat {
+    /// image, service, container and dir
+    Table,
+    /// table plus build file, created and pulled dates
+    Wide,
+    Json,
+}
+
+/// When to style output with color and bold
+#[derive(Clone, ValueEnum, Debug, Copy, PartialEq)]
+pub enum ColorWhen {
+    Auto,
+    Always,
+    Never,
+}
+
+/// Whose podman storage images live in
+#[derive(Clone, ValueEnum, Debug, Copy, PartialEq)]
+pub enum PodmanScope {
+    /// this user's rootless storage
+    User,
+    /// root's storage, through sudo
+    System,
+}
+
+/// Engines that can pull and build images
+#[derive(Clone, ValueEnum, Debug, Copy, PartialEq)]
+pub enum BuildEngine {
+    Podman,
+    Buildah,
+    Docker,
+}
+
+impl BuildEngine {
+    pub fn binary(&self) -> &'static str {
+        match self {
+            BuildEngine::Podman => "podman",
+            BuildEngine::Buildah => "buildah",
+            BuildEngine::Docker => "docker",
+        }
+    }
+
+    /// The binary for commands on stored images, such as save, load, tag and push. buildah shares
+    /// podman's storage but has no save or load.
+    pub fn store_binary(&self) -> &'static str {
+        match self {
+            BuildEngine::Podman | BuildEngine::Buildah => "podman",
+            BuildEngine::Docker => "docker",
+        }
+    }
+}
+
+/// How dates are shown
+#[derive(Clone, ValueEnum, Debug, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TimeFormat {
+    #[default]
+    Relative,
+    Absolute,
+    Both,
+}
+
+/// `<n>s`, `<n>m`, `<n>h`, `<n>d` or `<n>w`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("'{}' doesn't start with a number", value))?;
+    let secs = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(format!("unknown unit '{}', use s, m, h, d or w", unit)),
+    };
+    let secs = number
+        .checked_mul(secs)
+        .ok_or(format!("'{}' is too large a duration", value))?;
+    Ok(Duration::from_secs(secs))
 }
 
 // for a passed PathBuf, get the parent dir, check if it exists and is writable
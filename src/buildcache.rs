@@ -0,0 +1,47 @@
+use crate::args::Args;
+
+use regex::Regex;
+use std::path::Path;
+
+/// The registry cache ref to build `image` in `compose_dir` with: the first of the project's
+/// `[[build_cache.dirs]]` whose path_regex matches the dir, else --build-cache (or the project's
+/// `build_cache.ref`). An empty ref turns the cache off. `{name}` and `{tag}` are filled in from `image`.
+pub fn cache_ref(args: &Args, compose_dir: &Path, image: &str) -> Option<String> {
+    let dir = compose_dir.to_string_lossy();
+    let per_dir = args
+        .loaded_project
+        .as_ref()
+        .and_then(|p| p.build_cache.as_ref())
+        .and_then(|cache| {
+            cache.dirs.iter().find(|d| {
+                Regex::new(&d.path_regex)
+                    .map(|r| r.is_match(&dir))
+                    .unwrap_or(false)
+            })
+        })
+        .map(|d| d.cache_ref.as_str());
+    let template = per_dir.or(args.build_cache.as_deref())?;
+    if template.is_empty() {
+        return None;
+    }
+    let (name, tag) = split_image(image);
+    Some(template.replace("{name}", name).replace("{tag}", tag))
+}
+
+/// `registry.example.com:5000/derek/app:1.2` -> (`derek/app`, `1.2`); the tag defaults to `latest`.
+fn split_image(image: &str) -> (&str, &str) {
+    let name = image.split_once('@').map(|(name, _)| name).unwrap_or(image);
+    let name = match name.split_once('/') {
+        Some((registry, rest))
+            if registry.contains('.') || registry.contains(':') || registry == "localhost" =>
+        {
+            rest
+        }
+        _ => name,
+    };
+    let last_slash = name.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match name[last_slash..].split_once(':') {
+        Some((_, tag)) => (&name[..name.len() - tag.len() - 1], tag),
+        None => (name, "latest"),
+    }
+}
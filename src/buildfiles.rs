@@ -0,0 +1,52 @@
+use crate::state::StateDir;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+const CHOICES_FILE: &str = "buildfiles.json";
+
+/// Which build file to use in dirs with more than one, as picked at the prompt. Kept in the state
+/// dir as absolute dir -> file name, so the question is only asked once per dir.
+pub struct BuildfileChoices {
+    file: PathBuf,
+    choices: BTreeMap<PathBuf, String>,
+}
+
+impl BuildfileChoices {
+    pub fn load(state: &StateDir) -> Result<BuildfileChoices, String> {
+        let file = state.path().join("choices").join(CHOICES_FILE);
+        let choices = if file.exists() {
+            let content = fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read '{}': {}", file.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", file.display(), e))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(BuildfileChoices { file, choices })
+    }
+
+    /// The build file picked for `dir`, if one was and it's still there.
+    pub fn get(&self, dir: &Path) -> Option<PathBuf> {
+        let dir = path::absolute(dir).ok()?;
+        let chosen = dir.join(self.choices.get(&dir)?);
+        chosen.is_file().then_some(chosen)
+    }
+
+    /// Remember `buildfile` for its dir and save right away. Returns the file it was saved to.
+    pub fn set(&mut self, buildfile: &Path) -> Result<&Path, String> {
+        let buildfile = path::absolute(buildfile)
+            .map_err(|e| format!("Failed to resolve '{}': {}", buildfile.display(), e))?;
+        let (Some(dir), Some(name)) = (buildfile.parent(), buildfile.file_name()) else {
+            return Err(format!("'{}' isn't a file in a dir", buildfile.display()));
+        };
+        self.choices
+            .insert(dir.to_path_buf(), name.to_string_lossy().to_string());
+        let json = serde_json::to_string_pretty(&self.choices)
+            .map_err(|e| format!("Failed to serialize build file choices: {}", e))?;
+        fs::write(&self.file, json)
+            .map_err(|e| format!("Failed to write '{}': {}", self.file.display(), e))?;
+        Ok(&self.file)
+    }
+}
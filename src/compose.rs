@@ -0,0 +1,587 @@
+use crate::args;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Compose file names, in the order compose looks for them in a dir.
+pub const COMPOSE_FILE_NAMES: [&str; 4] = [
+    "compose.yaml",
+    "compose.yml",
+    "docker-compose.yaml",
+    "docker-compose.yml",
+];
+/// Service key holding this program's per-service settings, see `ServiceMeta`.
+pub const META_KEY: &str = "x-podman-compose-mgr";
+const OVERRIDE_FILE_NAMES: [&str; 4] = [
+    "compose.override.yaml",
+    "compose.override.yml",
+    "docker-compose.override.yaml",
+    "docker-compose.override.yml",
+];
+/// Sequences an override adds to rather than replaces, per the compose-spec merge rules.
+const MERGED_SEQUENCES: [&str; 10] = [
+    "ports",
+    "expose",
+    "external_links",
+    "dns",
+    "dns_search",
+    "tmpfs",
+    "cap_add",
+    "cap_drop",
+    "devices",
+    "volumes",
+];
+
+pub fn read_yaml_file(file: &Path) -> Result<Value, String> {
+    let f = File::open(file).map_err(|e| format!("Failed to open '{}': {}", file.display(), e))?;
+    serde_yaml::from_reader(f).map_err(|e| format!("Failed to parse '{}': {}", file.display(), e))
+}
+
+/// Whether `path` is the compose file compose would pick in its dir, i.e. a compose file name
+/// with no higher precedence one next to it.
+pub fn is_primary_compose_file(path: &Path) -> bool {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    COMPOSE_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|candidate| candidate.is_file())
+        .is_some_and(|primary| primary == path)
+}
+
+/// The override file compose merges over `compose_file`, if there is one.
+pub fn override_file(compose_file: &Path) -> Option<PathBuf> {
+    let dir = compose_file.parent().unwrap_or(Path::new("."));
+    OVERRIDE_FILE_NAMES
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+/// `-f` args naming the compose file and its override, since podman-compose skips the override when `-f` is given.
+pub fn file_args(compose_file: &Path) -> Vec<String> {
+    let mut args = vec!["-f".to_string(), compose_file.display().to_string()];
+    if let Some(override_file) = override_file(compose_file) {
+        args.push("-f".to_string());
+        args.push(override_file.display().to_string());
+    }
+    args
+}
+
+/// The effective config: `compose_file` with its override file merged over it.
+pub fn read_compose(compose_file: &Path) -> Result<Value, String> {
+    let mut yaml = read_yaml_file(compose_file)?;
+    if let Some(override_file) = override_file(compose_file) {
+        merge(&mut yaml, read_yaml_file(&override_file)?, None);
+    }
+    Ok(yaml)
+}
+
+/// Keys of `service` that the override file sets, so details can say where a value came from.
+pub fn overridden_keys(compose_file: &Path, service: &str) -> Vec<String> {
+    let yaml = match override_file(compose_file).map(|f| read_yaml_file(&f)) {
+        Some(Ok(yaml)) => yaml,
+        _ => return vec![],
+    };
+    match yaml
+        .get("services")
+        .and_then(|s| s.get(service))
+        .and_then(|s| s.as_mapping())
+    {
+        Some(config) => config
+            .keys()
+            .filter_map(|k| k.as_str().map(|s| s.to_string()))
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Mappings merge key by key, MERGED_SEQUENCES gain the override's new items, anything else is replaced.
+fn merge(base: &mut Value, over: Value, key: Option<&str>) {
+    match (base, over) {
+        (Value::Mapping(base), Value::Mapping(over)) => {
+            for (k, v) in over {
+                let name = k.as_str().map(|s| s.to_string());
+                match base.get_mut(&k) {
+                    Some(existing) => merge(existing, v, name.as_deref()),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(over))
+            if key.is_some_and(|k| MERGED_SEQUENCES.contains(&k)) =>
+        {
+            for v in over {
+                if !base.contains(&v) {
+                    base.push(v);
+                }
+            }
+        }
+        (base, over) => *base = over,
+    }
+}
+
+/// Service names and their config, in file order.
+pub fn services(yaml: &Value) -> Vec<(String, &Value)> {
+    let mut result = vec![];
+    if let Some(services_map) = yaml.get("services").and_then(|s| s.as_mapping()) {
+        for (name, config) in services_map {
+            if let Some(name) = name.as_str() {
+                result.push((name.to_string(), config));
+            }
+        }
+    }
+    result
+}
+
+/// The project name compose would use: COMPOSE_PROJECT_NAME, else the top-level `name:`, else the
+/// compose file's dir name, lowercased and without anything but letters, digits, `-` and `_`.
+pub fn project_name(compose_file: &Path, yaml: &Value) -> String {
+    let name = std::env::var("COMPOSE_PROJECT_NAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+        .or_else(|| yaml.get("name").and_then(|n| n.as_str()).map(String::from))
+        .unwrap_or_else(|| {
+            compose_file
+                .parent()
+                .and_then(|dir| dir.file_name())
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default()
+        });
+    name.to_lowercase()
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect()
+}
+
+/// The name compose gives `key` of the top-level `volumes:` or `networks:` (`section`): its `name:`,
+/// the key itself when it's `external`, else `<project>_<key>`.
+pub fn resource_name(yaml: &Value, section: &str, key: &str, project: &str) -> String {
+    let declared = yaml.get(section).and_then(|s| s.get(key));
+    if let Some(name) = declared
+        .and_then(|d| d.get("name"))
+        .and_then(|n| n.as_str())
+    {
+        return name.to_string();
+    }
+    match declared.and_then(|d| d.get("external")) {
+        // the older `external: {name: ...}` form
+        Some(external) if external.get("name").is_some() => {
+            external["name"].as_str().unwrap_or(key).to_string()
+        }
+        Some(Value::Bool(true)) => key.to_string(),
+        _ => format!("{}_{}", project, key),
+    }
+}
+
+/// Per-service settings, declared next to the service:
+///
+/// ```yaml
+/// x-podman-compose-mgr:
+///   skip: true
+///   build_args: [USERNAME=me]
+///   priority: 10
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServiceMeta {
+    /// Leave the service out of rebuilds.
+    pub skip: bool,
+    /// `--build-arg`s for this service's build, after the ones from the command line.
+    pub build_args: Vec<String>,
+    /// Services with a higher priority come up first within their compose file.
+    pub priority: i64,
+    /// In place of --build-timeout for this service's build, e.g. "2h"
+    pub build_timeout: Option<String>,
+}
+
+impl ServiceMeta {
+    pub fn build_timeout(&self) -> Option<Duration> {
+        // checked in service_meta
+        self.build_timeout
+            .as_deref()
+            .and_then(|t| args::parse_duration(t).ok())
+    }
+}
+
+/// The service's `x-podman-compose-mgr` block, defaults if it has none.
+pub fn service_meta(service_config: &Value) -> Result<ServiceMeta, String> {
+    let meta: ServiceMeta = match service_config.get(META_KEY) {
+        Some(meta) => serde_yaml::from_value(meta.clone())
+            .map_err(|e| format!("Invalid `{}`: {}", META_KEY, e))?,
+        None => ServiceMeta::default(),
+    };
+    if let Some(timeout) = &meta.build_timeout {
+        args::parse_duration(timeout)
+            .map_err(|e| format!("Invalid `{}` build_timeout: {}", META_KEY, e))?;
+    }
+    Ok(meta)
+}
+
+/// A service's `image`, with `${...}` interpolated, and the substitutions made.
+pub fn resolved_image(
+    compose_file: &Path,
+    service_config: &Value,
+) -> Option<(String, Vec<String>)> {
+    let image = service_config.get("image")?.as_str()?;
+    let vars = interpolation_vars(compose_file, service_config);
+    Some(interpolate(image, &vars))
+}
+
+/// Whether the service declares a `healthcheck` that isn't `disable: true`.
+pub fn has_healthcheck(service_config: &Value) -> bool {
+    match service_config.get("healthcheck") {
+        Some(healthcheck) => !healthcheck
+            .get("disable")
+            .and_then(|d| d.as_bool())
+            .unwrap_or(false),
+        None => false,
+    }
+}
+
+/// `depends_on` in either its short (list) or long (map of service -> condition) form.
+pub fn depends_on(service_config: &Value) -> Vec<String> {
+    match service_config.get("depends_on") {
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| v.as_str().map(|s| s.to_string()))
+            .collect(),
+        Some(Value::Mapping(map)) => map
+            .keys()
+            .filter_map(|k| k.as_str().map(|s| s.to_string()))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Host side of each `devices:` entry, in short (`/dev/dri:/dev/dri:rwm`) or long (`source:`) form.
+/// CDI names like `nvidia.com/gpu=all` are returned as is.
+pub fn devices(service_config: &Value) -> Vec<String> {
+    let mut result = vec![];
+    if let Some(Value::Sequence(seq)) = service_config.get("devices") {
+        for device in seq {
+            let host = match device.as_str() {
+                Some(s) if s.contains('=') => Some(s),
+                Some(s) => s.split(':').next(),
+                None => device.get("source").and_then(|v| v.as_str()),
+            };
+            if let Some(host) = host {
+                result.push(host.to_string());
+            }
+        }
+    }
+    result
+}
+
+pub fn is_privileged(service_config: &Value) -> bool {
+    service_config
+        .get("privileged")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Host ports a service publishes, from short (`[ip:]host:container[/proto]`, ranges like `8000-8001:80`)
+/// or long (`published:`) port entries.
+pub fn published_ports(service_config: &Value) -> Vec<u16> {
+    let mut result = vec![];
+    if let Some(Value::Sequence(seq)) = service_config.get("ports") {
+        for port in seq {
+            let published = match port {
+                Value::String(s) => {
+                    let s = s.split('/').next().unwrap_or(s);
+                    let parts: Vec<&str> = s.rsplitn(3, ':').collect();
+                    // a bare container port isn't published on a fixed host port
+                    if parts.len() < 2 {
+                        continue;
+                    }
+                    parts[1].to_string()
+                }
+                Value::Mapping(_) => match port.get("published") {
+                    Some(Value::Number(n)) => n.to_string(),
+                    Some(Value::String(s)) => s.clone(),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let start = published.split('-').next().unwrap_or(&published);
+            if let Ok(port) = start.trim().parse::<u16>() {
+                result.push(port);
+            }
+        }
+    }
+    result
+}
+
+/// GPU requests from `gpus:` and `deploy.resources.reservations.devices`, e.g. `driver nvidia, count all, capabilities gpu`.
+pub fn gpu_requests(service_config: &Value) -> Vec<String> {
+    let describe = |request: &Value| {
+        let mut parts = vec![];
+        for key in ["driver", "count", "device_ids", "capabilities"] {
+            let value = match request.get(key) {
+                Some(Value::Sequence(seq)) => seq
+                    .iter()
+                    .filter_map(scalar_to_string)
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                Some(v) => match scalar_to_string(v) {
+                    Some(v) => v,
+                    None => continue,
+                },
+                None => continue,
+            };
+            parts.push(format!("{} {}", key.replace('_', " "), value));
+        }
+        parts.join(", ")
+    };
+
+    let mut result = vec![];
+    match service_config.get("gpus") {
+        Some(Value::String(s)) => result.push(format!("gpus {}", s)),
+        Some(Value::Sequence(seq)) => result.extend(seq.iter().map(describe)),
+        _ => {}
+    }
+    if let Some(Value::Sequence(seq)) = service_config
+        .get("deploy")
+        .and_then(|d| d.get("resources"))
+        .and_then(|r| r.get("reservations"))
+        .and_then(|r| r.get("devices"))
+    {
+        result.extend(seq.iter().map(describe));
+    }
+    result
+}
+
+/// Where a `volumes:` entry's data comes from.
+pub enum MountSource {
+    /// a host path, absolute, with a relative one taken from the compose dir and `~` expanded
+    Bind(PathBuf),
+    /// a named volume
+    Volume(String),
+    /// an anonymous volume, just a container path
+    Anonymous,
+    Tmpfs,
+}
+
+/// One entry of a service's `volumes:`.
+pub struct ServiceMount {
+    pub source: MountSource,
+    pub target: String,
+    pub read_only: bool,
+    /// the short syntax's other options, such as `z`, as given
+    pub options: Vec<String>,
+}
+
+impl ServiceMount {
+    /// The bind source when it isn't there on this host. Compose creates a missing short-syntax
+    /// source as an empty dir, which is seldom what was meant either.
+    pub fn missing_bind_source(&self) -> Option<&Path> {
+        match &self.source {
+            MountSource::Bind(path) if !path.exists() => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ServiceMount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.source {
+            MountSource::Bind(path) => write!(f, "{} -> {}", path.display(), self.target)?,
+            MountSource::Volume(name) => write!(f, "volume {} -> {}", name, self.target)?,
+            MountSource::Anonymous => write!(f, "anonymous volume -> {}", self.target)?,
+            MountSource::Tmpfs => write!(f, "tmpfs -> {}", self.target)?,
+        }
+        if self.read_only {
+            write!(f, " (read-only)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The service's `volumes:`, in short (`./data:/data:ro`) or long (`type:`, `source:`, `target:`)
+/// form, interpolated. A short-form source is a bind mount when it's a path (`/`, `.` or `~`), else a named volume.
+pub fn mounts(compose_file: &Path, service_config: &Value) -> Vec<ServiceMount> {
+    let Some(Value::Sequence(seq)) = service_config.get("volumes") else {
+        return vec![];
+    };
+    let dir = compose_file.parent().unwrap_or(Path::new("."));
+    let vars = interpolation_vars(compose_file, service_config);
+    let interpolate = |s: &str| interpolate(s, &vars).0;
+    let bind = |source: &str| {
+        let path = match source
+            .strip_prefix('~')
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        {
+            Some(rest) => PathBuf::from(std::env::var("HOME").unwrap_or_default())
+                .join(rest.trim_start_matches('/')),
+            None => dir.join(source.trim_start_matches("./")),
+        };
+        MountSource::Bind(path)
+    };
+
+    let mut result = vec![];
+    for volume in seq {
+        let mount = match volume {
+            Value::String(s) => {
+                let s = interpolate(s);
+                let parts: Vec<&str> = s.splitn(3, ':').collect();
+                let all_options: Vec<&str> = parts
+                    .get(2)
+                    .map(|options| options.split(',').collect())
+                    .unwrap_or_default();
+                let read_only = all_options.contains(&"ro");
+                let options: Vec<String> = all_options
+                    .iter()
+                    .filter(|o| !["ro", "rw", ""].contains(o))
+                    .map(|o| o.to_string())
+                    .collect();
+                match parts[..] {
+                    [target] => ServiceMount {
+                        source: MountSource::Anonymous,
+                        target: target.to_string(),
+                        read_only,
+                        options,
+                    },
+                    [source, target, ..] => ServiceMount {
+                        source: if source.starts_with(['/', '.', '~']) {
+                            bind(source)
+                        } else {
+                            MountSource::Volume(source.to_string())
+                        },
+                        target: target.to_string(),
+                        read_only,
+                        options,
+                    },
+                    [] => continue,
+                }
+            }
+            Value::Mapping(_) => {
+                let Some(target) = volume.get("target").and_then(|t| t.as_str()) else {
+                    continue;
+                };
+                let source = volume
+                    .get("source")
+                    .and_then(|s| s.as_str())
+                    .map(interpolate);
+                ServiceMount {
+                    source: match (volume.get("type").and_then(|t| t.as_str()), source) {
+                        (Some("tmpfs"), _) => MountSource::Tmpfs,
+                        (Some("bind"), Some(source)) => bind(&source),
+                        (_, Some(source)) => MountSource::Volume(source),
+                        (_, None) => MountSource::Anonymous,
+                    },
+                    target: interpolate(target),
+                    read_only: volume.get("read_only").and_then(|r| r.as_bool()) == Some(true),
+                    options: vec![],
+                }
+            }
+            _ => continue,
+        };
+        result.push(mount);
+    }
+    result
+}
+
+fn scalar_to_string(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Variables available for interpolating `${...}` in a compose file, in compose's order of precedence:
+/// the shell environment, then the `.env` next to the compose file, then the service's `env_file:` entries.
+pub fn interpolation_vars(compose_file: &Path, service_config: &Value) -> HashMap<String, String> {
+    let dir = compose_file.parent().unwrap_or(Path::new("."));
+    let mut files = vec![dir.join(".env")];
+    match service_config.get("env_file") {
+        Some(Value::String(f)) => files.push(dir.join(f)),
+        Some(Value::Sequence(seq)) => {
+            for f in seq {
+                // long syntax is a map with a path key
+                if let Some(f) = f
+                    .as_str()
+                    .or_else(|| f.get("path").and_then(|p| p.as_str()))
+                {
+                    files.push(dir.join(f));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut vars = HashMap::new();
+    for file in files.iter().rev() {
+        vars.extend(read_env_file(file));
+    }
+    vars.extend(std::env::vars());
+    vars
+}
+
+/// KEY=VALUE lines of a .env file; a missing file is just empty.
+pub fn read_env_file(file: &Path) -> Vec<(String, String)> {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(_) => return vec![],
+    };
+    let mut result = vec![];
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        if let Some((key, value)) = line.split_once('=') {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+            result.push((key.trim().to_string(), value.to_string()));
+        }
+    }
+    result
+}
+
+/// Compose-style interpolation of `$VAR`, `${VAR}`, `${VAR:-default}`, `${VAR-default}`, `${VAR:?err}`
+/// and `$$`. Returns the interpolated string and the `VAR=value` substitutions made.
+pub fn interpolate(s: &str, vars: &HashMap<String, String>) -> (String, Vec<String>) {
+    let re = Regex::new(
+        r"\$(?:(\$)|\{([A-Za-z_][A-Za-z0-9_]*)(?:(:?[-?])([^}]*))?\}|([A-Za-z_][A-Za-z0-9_]*))",
+    )
+    .unwrap();
+    let mut substitutions = vec![];
+    let result = re.replace_all(s, |caps: &regex::Captures| {
+        if caps.get(1).is_some() {
+            return "$".to_string();
+        }
+        let name = caps.get(2).or_else(|| caps.get(5)).unwrap().as_str();
+        let value = vars.get(name);
+        let value = match (caps.get(3).map(|m| m.as_str()), value) {
+            // :- and :? treat empty like unset
+            (Some(":-"), Some(v)) if v.is_empty() => caps[4].to_string(),
+            (Some(":-"), None) | (Some("-"), None) => caps[4].to_string(),
+            (Some(":?"), Some(v)) if v.is_empty() => {
+                eprintln!("Variable {} is empty: {}", name, &caps[4]);
+                String::new()
+            }
+            (Some(":?"), None) | (Some("?"), None) => {
+                eprintln!("Variable {} is not set: {}", name, &caps[4]);
+                String::new()
+            }
+            (_, Some(v)) => v.clone(),
+            (_, None) => String::new(),
+        };
+        substitutions.push(format!("{}={}", name, value));
+        value
+    });
+    (result.to_string(), substitutions)
+}
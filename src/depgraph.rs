@@ -0,0 +1,180 @@
+use crate::compose;
+
+use serde_yaml::Value;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+pub struct ServiceNode {
+    pub name: String,
+    pub image: Option<String>,
+    pub container_name: Option<String>,
+    pub depends_on: Vec<String>,
+}
+
+/// The `depends_on` relationships between the services of one compose file.
+pub struct DependencyGraph {
+    pub compose_file: PathBuf,
+    pub services: Vec<ServiceNode>,
+}
+
+impl DependencyGraph {
+    pub fn from_yaml(compose_file: &Path, yaml: &Value) -> DependencyGraph {
+        let services = compose::services(yaml)
+            .into_iter()
+            .map(|(name, config)| ServiceNode {
+                image: compose::resolved_image(compose_file, config).map(|(image, _)| image),
+                container_name: config
+                    .get("container_name")
+                    .and_then(|c| c.as_str())
+                    .map(|c| c.to_string()),
+                depends_on: compose::depends_on(config),
+                name,
+            })
+            .collect();
+        DependencyGraph {
+            compose_file: compose_file.to_path_buf(),
+            services,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ServiceNode> {
+        self.services.iter().find(|s| s.name == name)
+    }
+
+    /// Services no other service depends on; the tops of the tree.
+    fn roots(&self) -> Vec<&ServiceNode> {
+        self.services
+            .iter()
+            .filter(|s| !self.services.iter().any(|o| o.depends_on.contains(&s.name)))
+            .collect()
+    }
+
+    /// Render as an indented tree, each service followed by what it depends on.
+    pub fn render_tree(&self) -> String {
+        let mut out = format!("{}\n", self.compose_file.display());
+        let mut roots = self.roots();
+        // everything is in a cycle, start somewhere so it still gets shown
+        if roots.is_empty() {
+            roots = self.services.iter().take(1).collect();
+        }
+        for root in roots {
+            out.push_str(&format!("  {}\n", root.name));
+            let mut path = vec![root.name.clone()];
+            self.render_children(root, "  ", &mut path, &mut out);
+        }
+        out
+    }
+
+    fn render_children(
+        &self,
+        node: &ServiceNode,
+        indent: &str,
+        path: &mut Vec<String>,
+        out: &mut String,
+    ) {
+        for (i, dep) in node.depends_on.iter().enumerate() {
+            let last = i == node.depends_on.len() - 1;
+            let branch = if last { "└── " } else { "├── " };
+            let child_indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            if path.contains(dep) {
+                out.push_str(&format!("{}{}{} (cycle)\n", indent, branch, dep));
+                continue;
+            }
+            match self.get(dep) {
+                Some(child) => {
+                    out.push_str(&format!("{}{}{}\n", indent, branch, dep));
+                    path.push(dep.clone());
+                    self.render_children(child, &child_indent, path, out);
+                    path.pop();
+                }
+                None => {
+                    out.push_str(&format!("{}{}{} (not defined)\n", indent, branch, dep));
+                }
+            }
+        }
+    }
+
+    /// The named services plus everything that depends on them, directly or transitively.
+    pub fn with_dependents(&self, names: &[String]) -> HashSet<String> {
+        let mut result: HashSet<String> = names.iter().cloned().collect();
+        loop {
+            let added: Vec<String> = self
+                .services
+                .iter()
+                .filter(|s| !result.contains(&s.name))
+                .filter(|s| s.depends_on.iter().any(|d| result.contains(d)))
+                .map(|s| s.name.clone())
+                .collect();
+            if added.is_empty() {
+                break;
+            }
+            result.extend(added);
+        }
+        result
+    }
+
+    /// Services ordered so each comes after everything it depends on.
+    /// Services caught in a cycle are appended at the end in file order.
+    pub fn dependency_order(&self) -> Vec<&ServiceNode> {
+        let mut ordered: Vec<&ServiceNode> = vec![];
+        let mut placed: HashSet<&str> = HashSet::new();
+        loop {
+            let ready: Vec<&ServiceNode> = self
+                .services
+                .iter()
+                .filter(|s| !placed.contains(s.name.as_str()))
+                .filter(|s| {
+                    s.depends_on
+                        .iter()
+                        .all(|d| placed.contains(d.as_str()) || self.get(d).is_none())
+                })
+                .collect();
+            if ready.is_empty() {
+                break;
+            }
+            for s in ready {
+                placed.insert(s.name.as_str());
+                ordered.push(s);
+            }
+        }
+        for s in &self.services {
+            if !placed.contains(s.name.as_str()) {
+                ordered.push(s);
+            }
+        }
+        ordered
+    }
+}
+
+/// Graphviz DOT for a set of compose files, one cluster per file.
+pub fn to_dot(graphs: &[DependencyGraph]) -> String {
+    let mut out = String::from("digraph compose {\n    rankdir=LR;\n");
+    for (i, graph) in graphs.iter().enumerate() {
+        let file = graph.compose_file.display().to_string();
+        out.push_str(&format!("    subgraph cluster_{} {{\n", i));
+        out.push_str(&format!("        label={};\n", dot_quote(&file)));
+        for service in &graph.services {
+            out.push_str(&format!(
+                "        {} [label={}];\n",
+                dot_quote(&format!("{}:{}", file, service.name)),
+                dot_quote(&service.name)
+            ));
+        }
+        out.push_str("    }\n");
+        for service in &graph.services {
+            for dep in &service.depends_on {
+                out.push_str(&format!(
+                    "    {} -> {};\n",
+                    dot_quote(&format!("{}:{}", file, service.name)),
+                    dot_quote(&format!("{}:{}", file, dep))
+                ));
+            }
+        }
+    }
+    out.push_str("}\n");
+    out
+}
+
+fn dot_quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
@@ -0,0 +1,159 @@
+use crate::args::Args;
+use crate::helpers::engine_helper_fns;
+use crate::helpers::output_helper_fns as output;
+use crate::helpers::redact_helper_fns as redact;
+use crate::state::StateDir;
+
+use chrono::Local;
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Flags whose values identify the Key Vault setup, or where the secrets and keys are, and are
+/// left out of the bundle.
+const SENSITIVE_FLAGS: [&str; 8] = [
+    "--secrets-client-id",
+    "--secrets-client-secret-path",
+    "--secrets-tenant-id",
+    "--secrets-vault-name",
+    "--secret-mode-input-json",
+    "--encryption-key-file",
+    "--signing-key-file",
+    "--audit-log",
+];
+const AUDIT_LOG_TAIL_LINES: usize = 100;
+
+/// Gather what's useful in a bug report into a tarball: versions, the command line and project file
+/// with secrets removed, `podman info`, the build history and the tail of the audit log.
+pub fn create_bundle(args: &Args) -> Result<PathBuf, String> {
+    let output = match &args.diagnostics_output {
+        Some(output) => output.clone(),
+        None => output::default_output_path(
+            args,
+            "diagnostics",
+            &format!("pcm-diagnostics-{}", Local::now().format("%Y%m%d-%H%M%S")),
+            "tar.gz",
+        )?,
+    };
+    let staging = staging_dir()?;
+    let result = gather(args, &staging).and_then(|_| {
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(&output)
+            .arg("-C")
+            .arg(&staging)
+            .arg(".")
+            .status()
+            .map_err(|e| format!("Failed to execute tar: {}", e))?;
+        if status.success() {
+            Ok(output.clone())
+        } else {
+            Err(format!("tar failed writing '{}'", output.display()))
+        }
+    });
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// A new dir under the temp dir that only we can read, mkdtemp style: the name can't be guessed
+/// ahead of time, and one that already exists is never reused.
+fn staging_dir() -> Result<PathBuf, String> {
+    let mut last_error = String::new();
+    for _ in 0..10 {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(std::process::id());
+        hasher.write_u128(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos()),
+        );
+        let staging =
+            std::env::temp_dir().join(format!("pcm-diagnostics-{:016x}", hasher.finish()));
+        match fs::DirBuilder::new().mode(0o700).create(&staging) {
+            Ok(()) => return Ok(staging),
+            Err(e) => last_error = format!("Failed to create '{}': {}", staging.display(), e),
+        }
+    }
+    Err(last_error)
+}
+
+fn gather(args: &Args, staging: &Path) -> Result<(), String> {
+    let write = |name: &str, content: &str| {
+        let path = staging.join(name);
+        fs::write(&path, redact::redact(content))
+            .map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+    };
+
+    let mut versions = format!("podman-compose-mgr {}\n", env!("CARGO_PKG_VERSION"));
+    for (cmd, cmd_args) in [
+        ("podman", vec!["--version"]),
+        ("podman-compose", vec!["version"]),
+        ("uname", vec!["-a"]),
+    ] {
+        versions.push_str(&format!("{}: {}\n", cmd, command_output(cmd, &cmd_args)));
+    }
+    write("versions.txt", &versions)?;
+    write("args.txt", &redacted_command_line())?;
+    write(
+        "podman-info.json",
+        &command_output("podman", &["info", "--format", "json"]),
+    )?;
+
+    if let Some(project) = &args.project {
+        let content = fs::read_to_string(project).unwrap_or_else(|e| e.to_string());
+        write("project.toml", &content)?;
+    }
+    if let Ok(state) = StateDir::open(args) {
+        if let Ok(history) = fs::read_to_string(state.path().join("history").join("images.json")) {
+            write("build-history.json", &history)?;
+        }
+    }
+    if let Some(audit_log) = &args.audit_log {
+        if let Ok(content) = fs::read_to_string(audit_log) {
+            let lines: Vec<&str> = content.lines().collect();
+            let tail = &lines[lines.len().saturating_sub(AUDIT_LOG_TAIL_LINES)..];
+            write("audit-log-tail.jsonl", &tail.join("\n"))?;
+        }
+    }
+    Ok(())
+}
+
+/// The command line, with the values of SENSITIVE_FLAGS replaced.
+fn redacted_command_line() -> String {
+    let mut result = vec![];
+    let mut redact_next = false;
+    for arg in std::env::args() {
+        if redact_next {
+            result.push("<redacted>".to_string());
+            redact_next = false;
+            continue;
+        }
+        match SENSITIVE_FLAGS
+            .iter()
+            .find(|flag| arg == **flag || arg.starts_with(&format!("{}=", flag)))
+        {
+            Some(flag) if arg.contains('=') => result.push(format!("{}=<redacted>", flag)),
+            Some(_) => {
+                result.push(arg);
+                redact_next = true;
+            }
+            None => result.push(arg),
+        }
+    }
+    result.join(" ") + "\n"
+}
+
+/// stdout of a command, or why it couldn't be run; a bundle should be made even if podman is broken.
+fn command_output(cmd: &str, args: &[&str]) -> String {
+    match engine_helper_fns::command(cmd).args(args).output() {
+        Ok(output) if output.status.success() => {
+            String::from_utf8_lossy(&output.stdout).trim().to_string()
+        }
+        Ok(output) => format!("failed: {}", String::from_utf8_lossy(&output.stderr).trim()),
+        Err(e) => format!("failed: {}", e),
+    }
+}
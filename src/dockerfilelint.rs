@@ -0,0 +1,155 @@
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::dockerfile_helper_fns::{self, DockerfileInstruction};
+use crate::helpers::engine_helper_fns;
+use crate::lint::{Finding, Severity};
+
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Command;
+
+/// A finding from `hadolint --format json`.
+#[derive(Deserialize)]
+struct HadolintFinding {
+    line: usize,
+    code: String,
+    level: String,
+    message: String,
+}
+
+/// Lint a Dockerfile or Containerfile with hadolint when it's on PATH, else with the built-in rules:
+/// unpinned base images, ADD where COPY would do, and apt-get installs that leave the package lists behind.
+pub fn lint_buildfile(file: &Path) -> Vec<Finding> {
+    if engine_helper_fns::on_path("hadolint") {
+        match hadolint(file) {
+            Ok(findings) => return findings,
+            Err(e) => color::warn(format!("{}, using the built-in rules instead.", e)),
+        }
+    }
+    match dockerfile_helper_fns::read_instructions(file) {
+        Ok(instructions) => builtin_rules(file, &instructions),
+        Err(e) => vec![Finding {
+            file: file.to_path_buf(),
+            line: 0,
+            severity: Severity::Error,
+            message: e,
+        }],
+    }
+}
+
+fn hadolint(file: &Path) -> Result<Vec<Finding>, String> {
+    // hadolint exits non-zero when it finds anything, so only unparseable output is a failure
+    let output = Command::new("hadolint")
+        .args(["--no-fail", "--format", "json"])
+        .arg(file)
+        .output()
+        .map_err(|e| format!("Failed to execute hadolint: {}", e))?;
+    let found: Vec<HadolintFinding> = serde_json::from_slice(&output.stdout).map_err(|e| {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let reason = if stderr.is_empty() {
+            e.to_string()
+        } else {
+            stderr
+        };
+        format!("hadolint failed on '{}': {}", file.display(), reason)
+    })?;
+    Ok(found
+        .into_iter()
+        .map(|f| Finding {
+            file: file.to_path_buf(),
+            line: f.line,
+            severity: match f.level.as_str() {
+                "error" => Severity::Error,
+                "warning" => Severity::Warning,
+                _ => Severity::Info,
+            },
+            message: format!("{} [{}]", f.message, f.code),
+        })
+        .collect())
+}
+
+fn builtin_rules(file: &Path, instructions: &[DockerfileInstruction]) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut add = |line: usize, message: String| {
+        findings.push(Finding {
+            file: file.to_path_buf(),
+            line,
+            severity: Severity::Warning,
+            message,
+        })
+    };
+    // `FROM x AS name` stages, which later FROMs can refer to without a tag
+    let mut stages: HashSet<String> = HashSet::new();
+
+    for instruction in instructions {
+        let words: Vec<&str> = instruction.args.split_whitespace().collect();
+        match instruction.keyword.as_str() {
+            "FROM" => {
+                let image = words
+                    .iter()
+                    .find(|w| !w.starts_with("--"))
+                    .copied()
+                    .unwrap_or("");
+                if let Some(pos) = words.iter().position(|w| w.eq_ignore_ascii_case("as")) {
+                    if let Some(stage) = words.get(pos + 1) {
+                        stages.insert(stage.to_lowercase());
+                    }
+                }
+                if image == "scratch"
+                    || image.contains('$')
+                    || stages.contains(&image.to_lowercase())
+                {
+                    continue;
+                }
+                let (name, digest) = match image.split_once('@') {
+                    Some((name, digest)) => (name, Some(digest)),
+                    None => (image, None),
+                };
+                let tag = name
+                    .rsplit('/')
+                    .next()
+                    .and_then(|last| last.split_once(':'))
+                    .map(|(_, tag)| tag);
+                if digest.is_none() && tag.is_none_or(|tag| tag == "latest") {
+                    add(
+                        instruction.line,
+                        format!(
+                            "base image `{}` isn't pinned, give it a version tag or digest [unpinned-base]",
+                            image
+                        ),
+                    );
+                }
+            }
+            "ADD" => {
+                let sources: Vec<&&str> = words.iter().filter(|w| !w.starts_with("--")).collect();
+                let sources = &sources[..sources.len().saturating_sub(1)];
+                // ADD is for fetching URLs and unpacking local archives
+                let needs_add = sources.iter().any(|s| {
+                    s.contains("://")
+                        || [".tar", ".tar.gz", ".tgz", ".tar.bz2", ".tar.xz"]
+                            .iter()
+                            .any(|ext| s.ends_with(ext))
+                });
+                if !sources.is_empty() && !needs_add {
+                    add(
+                        instruction.line,
+                        "ADD of local files, COPY does the same without the URL and archive handling [add-for-copy]"
+                            .to_string(),
+                    );
+                }
+            }
+            "RUN" => {
+                let run = &instruction.args;
+                if run.contains("apt-get install") && !run.contains("/var/lib/apt/lists") {
+                    add(
+                        instruction.line,
+                        "apt-get install without `rm -rf /var/lib/apt/lists/*` in the same RUN leaves the package lists in the layer [apt-no-cleanup]"
+                            .to_string(),
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+    findings
+}
@@ -0,0 +1,196 @@
+use crate::args::{Args, BuildEngine};
+use crate::helpers::engine_helper_fns;
+use crate::helpers::podman_helper_fns;
+use crate::state::StateDir;
+
+use serde_json::Value;
+use std::fs;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::time::Duration;
+
+enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Check the environment this program depends on and print a pass/fail table. Checks for optional
+/// features (podman-compose, trivy, the Key Vault files, the registry auth file) only run when
+/// the args enable them. Returns whether nothing failed.
+pub fn run(args: &Args) -> bool {
+    let mut all_ok = true;
+    let mut report = |check: &str, status: Status, detail: String| {
+        let tag = match status {
+            Status::Ok => "[ ok ]",
+            Status::Warn => "[warn]",
+            Status::Fail => {
+                all_ok = false;
+                "[FAIL]"
+            }
+        };
+        println!("{} {:<18} {}", tag, check, detail);
+    };
+
+    let podman_info = match command_output("podman", &["info", "--format", "json"]) {
+        Ok(output) => {
+            match command_output("podman", &["version", "--format", "{{.Client.Version}}"]) {
+                Ok(version) => report("podman", Status::Ok, format!("version {}", version)),
+                Err(e) => report("podman", Status::Warn, e),
+            }
+            serde_json::from_str::<Value>(&output).ok()
+        }
+        Err(e) => {
+            report("podman", Status::Fail, e);
+            None
+        }
+    };
+
+    if let Some(info) = &podman_info {
+        match podman_helper_fns::is_rootless() {
+            Ok(true) => report("rootless", Status::Ok, "yes".to_string()),
+            Ok(false) => report(
+                "rootless",
+                Status::Ok,
+                "no, podman runs as root".to_string(),
+            ),
+            Err(e) => report("rootless", Status::Warn, e),
+        }
+        match info["store"]["graphDriverName"].as_str() {
+            Some("vfs") => report(
+                "storage driver",
+                Status::Warn,
+                "vfs, builds will be slow and use a lot of disk".to_string(),
+            ),
+            Some(driver) => report("storage driver", Status::Ok, driver.to_string()),
+            None => report(
+                "storage driver",
+                Status::Warn,
+                "not in podman info".to_string(),
+            ),
+        }
+        let registries: Vec<&str> = info["registries"]["search"]
+            .as_array()
+            .map(|a| a.iter().filter_map(|r| r.as_str()).collect())
+            .unwrap_or_default();
+        for registry in registries {
+            match can_connect(registry) {
+                Ok(()) => report("registry", Status::Ok, format!("{} reachable", registry)),
+                Err(e) => report("registry", Status::Fail, format!("{}: {}", registry, e)),
+            }
+        }
+    }
+
+    let engine = engine_helper_fns::engine();
+    if engine != BuildEngine::Podman {
+        match command_output(engine.binary(), &["--version"]) {
+            Ok(version) => report("build engine", Status::Ok, version),
+            Err(e) => report("build engine", Status::Fail, e),
+        }
+    }
+
+    if args.restart_after_build {
+        match command_output("podman-compose", &["version"]) {
+            Ok(_) => report("podman-compose", Status::Ok, "found".to_string()),
+            Err(e) => report("podman-compose", Status::Fail, e),
+        }
+    }
+    if args.scan_after_build {
+        match command_output("trivy", &["--version"]) {
+            Ok(version) => report(
+                "trivy",
+                Status::Ok,
+                version.lines().next().unwrap_or("").to_string(),
+            ),
+            Err(e) => report("trivy", Status::Fail, e),
+        }
+    }
+
+    if let Some(auth_file) = args
+        .loaded_project
+        .as_ref()
+        .and_then(|p| p.registry.as_ref())
+        .and_then(|r| r.auth_file.as_ref())
+    {
+        report_readable(&mut report, "registry auth", auth_file);
+    }
+    if let Some(client_secret) = &args.secrets_client_secret_path {
+        report_readable(&mut report, "kv client secret", client_secret);
+    }
+    if let Some(input_json) = &args.secret_mode_input_json {
+        report_readable(&mut report, "secrets input", input_json);
+    }
+    if let Some(key_file) = &args.encryption_key_file {
+        report_readable(&mut report, "encryption key", key_file);
+    }
+    if let Some(key_file) = &args.signing_key_file {
+        report_readable(&mut report, "signing key", key_file);
+    }
+    if let Some(key_file) = &args.verify_key_file {
+        report_readable(&mut report, "verify key", key_file);
+    }
+
+    match StateDir::open(args).and_then(|state| check_writable(state.path())) {
+        Ok(dir) => report("state dir", Status::Ok, format!("{} writable", dir)),
+        Err(e) => report("state dir", Status::Fail, e),
+    }
+    match check_writable(&std::env::temp_dir()) {
+        Ok(dir) => report("temp dir", Status::Ok, format!("{} writable", dir)),
+        Err(e) => report("temp dir", Status::Fail, e),
+    }
+
+    all_ok
+}
+
+fn report_readable(report: &mut impl FnMut(&str, Status, String), check: &str, file: &Path) {
+    match fs::read(file) {
+        Ok(_) => report(check, Status::Ok, format!("{} readable", file.display())),
+        Err(e) => report(check, Status::Fail, format!("{}: {}", file.display(), e)),
+    }
+}
+
+/// Trimmed stdout of a command that has to succeed.
+fn command_output(cmd: &str, args: &[&str]) -> Result<String, String> {
+    let output = engine_helper_fns::command(cmd)
+        .args(args)
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => format!("{} not found on PATH", cmd),
+            _ => format!("Failed to execute {}: {}", cmd, e),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} {} failed: {}",
+            cmd,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// TCP connect to the registry's https port. Registries can be `host[:port][/namespace]`.
+fn can_connect(registry: &str) -> Result<(), String> {
+    let host = registry.split('/').next().unwrap_or(registry);
+    let host = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:443", host)
+    };
+    let addr = host
+        .to_socket_addrs()
+        .map_err(|e| format!("can't resolve: {}", e))?
+        .next()
+        .ok_or("can't resolve".to_string())?;
+    TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+fn check_writable(dir: &Path) -> Result<String, String> {
+    let probe = dir.join(".pcm_write_check");
+    fs::write(&probe, b"")
+        .and_then(|_| fs::remove_file(&probe))
+        .map_err(|e| format!("{} not writable: {}", dir.display(), e))?;
+    Ok(dir.display().to_string())
+}
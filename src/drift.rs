@@ -0,0 +1,138 @@
+use crate::list::ListRow;
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One difference between the baseline and the current scan.
+#[derive(Serialize)]
+pub struct Change {
+    /// new, removed, image, build-file or created-regressed
+    pub kind: &'static str,
+    pub service: String,
+    pub dir: PathBuf,
+    pub detail: String,
+}
+
+/// A `list --format json` file.
+pub fn load_baseline(file: &Path) -> Result<Vec<ListRow>, String> {
+    let content = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read '{}': {}", file.display(), e))?;
+    serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse '{}': {}", file.display(), e))
+}
+
+/// Services are matched on their compose dir and name. An image created earlier than the
+/// baseline's means a rollback or a stale pull.
+pub fn compare(baseline: &[ListRow], current: &[ListRow]) -> Vec<Change> {
+    let key = |row: &ListRow| (row.dir.clone(), row.service.clone());
+    let before: BTreeMap<(PathBuf, String), &ListRow> =
+        baseline.iter().map(|row| (key(row), row)).collect();
+    let after: BTreeMap<(PathBuf, String), &ListRow> =
+        current.iter().map(|row| (key(row), row)).collect();
+    let change = |kind, row: &ListRow, detail| Change {
+        kind,
+        service: row.service.clone(),
+        dir: row.dir.clone(),
+        detail,
+    };
+    let build_file_name = |row: &ListRow| {
+        row.build_file
+            .as_ref()
+            .and_then(|f| f.file_name())
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_else(|| "none".to_string())
+    };
+
+    let mut changes = vec![];
+    for (k, was) in &before {
+        if !after.contains_key(k) {
+            let detail = match &was.container {
+                Some(container) => format!("container {} ({})", container, was.image),
+                None => was.image.clone(),
+            };
+            changes.push(change("removed", was, detail));
+        }
+    }
+    for (k, now) in &after {
+        let Some(was) = before.get(k) else {
+            changes.push(change("new", now, now.image.clone()));
+            continue;
+        };
+        if was.image != now.image {
+            changes.push(change(
+                "image",
+                now,
+                format!("{} -> {}", was.image, now.image),
+            ));
+        }
+        if was.build_file != now.build_file {
+            changes.push(change(
+                "build-file",
+                now,
+                format!("{} -> {}", build_file_name(was), build_file_name(now)),
+            ));
+        }
+        if let (Some(was_created), Some(now_created)) = (was.created, now.created) {
+            if was.image == now.image && now_created < was_created {
+                changes.push(change(
+                    "created-regressed",
+                    now,
+                    format!(
+                        "{} created {}, baseline had {}",
+                        now.image,
+                        now_created.format("%Y-%m-%d %H:%M"),
+                        was_created.format("%Y-%m-%d %H:%M")
+                    ),
+                ));
+            }
+        }
+    }
+    changes
+}
+
+/// Print the changes as a table, or a JSON array with `json`. Returns whether there were none.
+pub fn report(changes: &[Change], json: bool) -> bool {
+    if json {
+        println!("{}", serde_json::to_string_pretty(changes).unwrap());
+        return changes.is_empty();
+    }
+    if changes.is_empty() {
+        println!("No drift from the baseline.");
+        return true;
+    }
+    let mut table = vec![vec![
+        "KIND".to_string(),
+        "SERVICE".to_string(),
+        "DIR".to_string(),
+        "DETAIL".to_string(),
+    ]];
+    for change in changes {
+        table.push(vec![
+            change.kind.to_string(),
+            change.service.clone(),
+            change.dir.display().to_string(),
+            change.detail.clone(),
+        ]);
+    }
+    let widths: Vec<usize> = (0..4)
+        .map(|i| {
+            table
+                .iter()
+                .map(|r| r[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for cells in &table {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+    println!("{} change(s) from the baseline.", changes.len());
+    false
+}
@@ -0,0 +1,90 @@
+use crate::exit_code;
+
+use std::error::Error;
+use std::fmt;
+
+/// Why a run ended unsuccessfully, for its exit code and the last thing the user reads. Detail
+/// already printed along the way (lint findings, podman output, mismatched files) isn't repeated.
+#[derive(Debug)]
+pub enum AppError {
+    /// Bad arguments, project file or a file they point to
+    Usage(String),
+    /// How many scan roots couldn't be read
+    Discovery(usize),
+    /// How many pulls or builds failed
+    Build(u32),
+    /// Key Vault, or a setting needed to reach it, rejected the credentials
+    SecretsAuth,
+    /// How many files `secrets retrieve` found differing from the vault
+    SecretsMismatch(usize),
+    /// A secrets operation that stopped partway: what it was doing and why
+    Secrets(&'static str, Box<dyn Error>),
+    /// lint, repo-check, drift, doctor or retag found problems, and printed them
+    Findings,
+    /// Anything else that stopped a mode: what it was doing and why
+    Failed(&'static str, String),
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Usage(_) => exit_code::USAGE,
+            AppError::Discovery(_) => exit_code::DISCOVERY,
+            AppError::Build(_) => exit_code::BUILD,
+            AppError::SecretsAuth => exit_code::SECRETS_AUTH,
+            AppError::SecretsMismatch(_) => exit_code::SECRETS_MISMATCH,
+            AppError::Secrets(..) | AppError::Findings | AppError::Failed(..) => exit_code::FAILURE,
+        }
+    }
+
+    /// What to try next, where there's something better to say than the error itself.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            AppError::Usage(_) => Some("`podman-compose-mgr help <subcommand>` lists its options."),
+            AppError::Discovery(_) => {
+                Some("Check that each --path, and each path in the project file, exists and is readable.")
+            }
+            AppError::Build(_) => Some(
+                "podman's output above says why. `diagnostics` bundles the logs and build history for a bug report.",
+            ),
+            AppError::SecretsAuth => Some(
+                "Check --secrets-client-id, --secrets-tenant-id, --secrets-vault-name and the file --secrets-client-secret-path points to.",
+            ),
+            AppError::SecretsMismatch(_) => Some(
+                "Upload the local files with `secrets upload` if they're right, or restore them from the vault.",
+            ),
+            AppError::Secrets(..) | AppError::Findings | AppError::Failed(..) => None,
+        }
+    }
+
+    /// Print the error and its hint to stderr. Findings were printed as they were found, so they print nothing here.
+    pub fn report(&self) {
+        match self {
+            AppError::Findings => return,
+            AppError::Secrets(..) | AppError::Failed(..) => eprintln!("Error {}", self),
+            _ => eprintln!("Error: {}", self),
+        }
+        if let Some(hint) = self.hint() {
+            eprintln!("Hint: {}", hint);
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Usage(message) => write!(f, "{}", message),
+            AppError::Discovery(roots) => write!(f, "{} scan root(s) couldn't be read", roots),
+            AppError::Build(failed) => write!(f, "{} pull(s) or build(s) failed", failed),
+            AppError::SecretsAuth => write!(f, "the secrets credentials didn't check out"),
+            AppError::SecretsMismatch(files) => {
+                write!(f, "{} file(s) don't match the vault", files)
+            }
+            AppError::Secrets(doing, e) => write!(f, "{}: {}", doing, e),
+            AppError::Findings => write!(f, "found problems"),
+            AppError::Failed(doing, e) => write!(f, "{}: {}", doing, e),
+        }
+    }
+}
+
+impl Error for AppError {}
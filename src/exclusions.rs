@@ -0,0 +1,58 @@
+use crate::state::StateDir;
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+
+const EXCLUSIONS_FILE: &str = "paths.json";
+
+/// Dirs excluded from scans with the `x` prompt choice, kept in the state dir so they stay excluded
+/// in later runs. Paths are stored absolute, so the scan can be started from anywhere. The scan and
+/// the rebuild prompt share one, so a dir excluded at the prompt is skipped for the rest of the walk.
+pub struct Exclusions {
+    file: PathBuf,
+    paths: RefCell<HashSet<PathBuf>>,
+}
+
+impl Exclusions {
+    pub fn load(state: &StateDir) -> Result<Exclusions, String> {
+        let file = state.path().join("skip").join(EXCLUSIONS_FILE);
+        let paths = if file.exists() {
+            let content = fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read '{}': {}", file.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", file.display(), e))?
+        } else {
+            HashSet::new()
+        };
+        Ok(Exclusions {
+            file,
+            paths: RefCell::new(paths),
+        })
+    }
+
+    /// Whether `path` is an excluded dir or inside one.
+    pub fn covers(&self, path: &Path) -> bool {
+        let paths = self.paths.borrow();
+        !paths.is_empty()
+            && path::absolute(path)
+                .map(|path| path.ancestors().any(|dir| paths.contains(dir)))
+                .unwrap_or(false)
+    }
+
+    /// Exclude `dir` and save right away. Returns the file it was saved to, for undoing by hand.
+    pub fn add(&self, dir: &Path) -> Result<&Path, String> {
+        let dir = path::absolute(dir)
+            .map_err(|e| format!("Failed to resolve '{}': {}", dir.display(), e))?;
+        let mut paths = self.paths.borrow_mut();
+        paths.insert(dir);
+        let mut sorted: Vec<&PathBuf> = paths.iter().collect();
+        sorted.sort();
+        let json = serde_json::to_string_pretty(&sorted)
+            .map_err(|e| format!("Failed to serialize exclusions: {}", e))?;
+        fs::write(&self.file, json)
+            .map_err(|e| format!("Failed to write '{}': {}", self.file.display(), e))?;
+        Ok(&self.file)
+    }
+}
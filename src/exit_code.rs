@@ -0,0 +1,16 @@
+// Exit codes, so wrappers and systemd units can tell failures apart. Keep doc/README.md in sync.
+
+/// Anything without a more specific code, e.g. lint, repo-check or doctor found errors.
+pub const FAILURE: i32 = 1;
+/// Bad arguments, project file or a file they point to; clap uses 2 for its own parse errors too.
+pub const USAGE: i32 = 2;
+/// A scan root couldn't be read.
+pub const DISCOVERY: i32 = 3;
+/// A pull or build failed.
+pub const BUILD: i32 = 4;
+/// Key Vault rejected the credentials.
+pub const SECRETS_AUTH: i32 = 5;
+/// secrets retrieve found files that don't match what's in the vault.
+pub const SECRETS_MISMATCH: i32 = 6;
+/// Stopped by a signal, plus the signal number: 129 for SIGHUP, 143 for SIGTERM, as shells report it.
+pub const SIGNAL_BASE: i32 = 128;
@@ -0,0 +1,83 @@
+use crate::args::Args;
+use crate::helpers::redact_helper_fns as redact;
+
+use chrono::Local;
+use md5::{Digest, Md5};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+
+/// One line of the audit log. `prev_md5` is the md5 of the previous line, so edits or
+/// deletions in the middle of the log break the chain and are detectable.
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    ts: String,
+    hostname: String,
+    action: &'a str,
+    target: &'a str,
+    outcome: &'a str,
+    prev_md5: String,
+}
+
+/// Append a record to the `--audit-log` file, if one was given. Failing to write the log is reported but isn't fatal.
+pub fn record(args: &Args, action: &str, target: &str, outcome: &str) {
+    if let Some(audit_log) = &args.audit_log {
+        record_to(audit_log, action, target, outcome);
+    }
+}
+
+/// `record`, for callers without the args at hand.
+pub fn record_to(audit_log: &Path, action: &str, target: &str, outcome: &str) {
+    let target = redact::redact(target);
+    let outcome = redact::redact(outcome);
+    if let Err(e) = append_record(audit_log, action, &target, &outcome) {
+        eprintln!("Error writing audit log {}: {}", audit_log.display(), e);
+    }
+}
+
+pub fn outcome(success: bool) -> &'static str {
+    if success {
+        "ok"
+    } else {
+        "failed"
+    }
+}
+
+fn append_record(
+    audit_log: &Path,
+    action: &str,
+    target: &str,
+    outcome: &str,
+) -> Result<(), String> {
+    let prev_md5 = match fs::read_to_string(audit_log) {
+        Ok(content) => match content.lines().last() {
+            Some(last) => {
+                let mut hasher = Md5::new();
+                hasher.update(last);
+                format!("{:x}", hasher.finalize())
+            }
+            None => String::new(),
+        },
+        Err(_) => String::new(),
+    };
+
+    let record = AuditRecord {
+        ts: Local::now().to_rfc3339(),
+        hostname: hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        action,
+        target,
+        outcome,
+        prev_md5,
+    };
+    let line = serde_json::to_string(&record).map_err(|e| e.to_string())?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(audit_log)
+        .map_err(|e| e.to_string())?;
+    writeln!(file, "{}", line).map_err(|e| e.to_string())
+}
@@ -1,6 +1,20 @@
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::engine_helper_fns;
+use crate::helpers::log_sink_helper_fns as log_sink;
+use crate::helpers::redact_helper_fns as redact;
+use crate::helpers::signal_helper_fns;
+
 use dockerfile_parser::Dockerfile;
 use std::io::{BufRead, BufReader, Read};
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// How long a command sent SIGTERM for running past its timeout gets to exit before SIGKILL.
+const KILL_GRACE: Duration = Duration::from_secs(10);
 
 pub fn pull_base_image(dockerfile: &std::path::PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     let file = std::fs::File::open(dockerfile).unwrap();
@@ -24,7 +38,7 @@ pub fn pull_base_image(dockerfile: &std::path::PathBuf) -> Result<(), Box<dyn st
     }
     x.push(&img_nm[0]);
 
-    exec_cmd("podman", x);
+    exec_cmd(engine_helper_fns::engine().binary(), x);
 
     Ok(())
 }
@@ -33,29 +47,139 @@ pub fn dockerfile_exists_and_readable(dockerfile: &std::path::PathBuf) -> bool {
     dockerfile.exists() && dockerfile.is_file() && dockerfile.metadata().is_ok()
 }
 
-pub fn exec_cmd(cmd: &str, args: Vec<&str>) {
-    let mut cmd = Command::new(cmd);
+pub fn exec_cmd(cmd: &str, args: Vec<&str>) -> ExitStatus {
+    exec_cmd_with_timeout(cmd, args, None).0
+}
+
+/// `exec_cmd`, sending the command SIGTERM if it runs longer than `timeout`, and SIGKILL if it's still running
+/// `KILL_GRACE` after that. The bool says whether it timed out.
+pub fn exec_cmd_with_timeout(
+    cmd: &str,
+    args: Vec<&str>,
+    timeout: Option<Duration>,
+) -> (ExitStatus, bool) {
+    let mut cmd = engine_helper_fns::command(cmd);
 
     cmd.args(args);
 
     let mut x = cmd
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .spawn()
         .expect("Failed to execute command");
+    signal_helper_fns::set_child(x.id());
+
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let (done, finished) = mpsc::channel::<()>();
+    if let Some(timeout) = timeout {
+        let pid = x.id().to_string();
+        let timed_out = Arc::clone(&timed_out);
+        thread::spawn(move || {
+            if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(timeout) {
+                timed_out.store(true, Ordering::SeqCst);
+                let _ = Command::new("kill").args(["-TERM", &pid]).status();
+                if let Err(RecvTimeoutError::Timeout) = finished.recv_timeout(KILL_GRACE) {
+                    let _ = Command::new("kill").args(["-KILL", &pid]).status();
+                }
+            }
+        });
+    }
 
-    if let Some(stdout) = x.stdout.take() {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            if let Ok(line) = line {
+    // each pipe on its own thread, so a command filling one doesn't block on the other
+    let stdout = x.stdout.take().map(|stdout| {
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                let line = redact::redact(&sanitize_output_line(&line));
+                log_sink::mirror(&line);
                 println!("{}", line);
             }
+        })
+    });
+    let stderr = x.stderr.take().map(|stderr| {
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                let line = redact::redact(&sanitize_line(&line, color::stderr()));
+                log_sink::mirror_error(&line);
+                eprintln!("{}", line);
+            }
+        })
+    });
+
+    let status = x.wait().expect("Command wasn't running");
+    signal_helper_fns::clear_child();
+    let _ = done.send(());
+    // what a timed out command started can outlive it and hold its pipes open, so only wait
+    // for the rest of its output when it exited on its own
+    if !timed_out.load(Ordering::SeqCst) {
+        for reader in [stdout, stderr].into_iter().flatten() {
+            let _ = reader.join();
         }
     }
+    (status, timed_out.load(Ordering::SeqCst))
+}
 
-    let _ = x.wait().expect("Command wasn't running");
+/// Keep the color (SGR) escapes in a line of command output, unless --color/NO_COLOR say not to, but drop
+/// cursor movement, erase and title escapes, which would scribble over our own prompts. A progress line
+/// redrawn with `\r` keeps only its last state.
+pub fn sanitize_output_line(line: &str) -> String {
+    sanitize_line(line, color::stdout())
+}
+
+fn sanitize_line(line: &str, keep_color: bool) -> String {
+    let line = line.rsplit('\r').find(|s| !s.is_empty()).unwrap_or("");
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            if !c.is_control() || c == '\t' {
+                result.push(c);
+            }
+            continue;
+        }
+        match chars.next() {
+            // CSI: ESC [ params final-byte
+            Some('[') => {
+                let mut seq = String::from("\x1b[");
+                for c in chars.by_ref() {
+                    seq.push(c);
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
+                }
+                if keep_color && seq.ends_with('m') {
+                    result.push_str(&seq);
+                }
+            }
+            // OSC: ESC ] ... terminated by BEL or ESC \
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    result
 }
 
 pub fn get_terminal_display_width() -> usize {
     let (width, _) = term_size::dimensions().unwrap_or((80, 24));
     width
 }
+
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1000.0 && unit < units.len() - 1 {
+        size /= 1000.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, units[unit])
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
+}
@@ -0,0 +1,51 @@
+use crate::args::ColorWhen;
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+
+pub const BOLD: &str = "1";
+pub const DIM: &str = "2";
+pub const RED: &str = "31";
+pub const GREEN: &str = "32";
+pub const YELLOW: &str = "33";
+
+/// Set by --color.
+static WHEN: OnceLock<ColorWhen> = OnceLock::new();
+
+pub fn init(when: ColorWhen) {
+    let _ = WHEN.set(when);
+}
+
+/// --color always and never win; auto styles a terminal unless NO_COLOR is set to anything non-empty.
+fn enabled(is_terminal: bool) -> bool {
+    match WHEN.get().copied().unwrap_or(ColorWhen::Auto) {
+        ColorWhen::Always => true,
+        ColorWhen::Never => false,
+        ColorWhen::Auto => is_terminal && std::env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// Whether what we print to stdout may be styled.
+pub fn stdout() -> bool {
+    enabled(io::stdout().is_terminal())
+}
+
+/// Whether what we print to stderr may be styled.
+pub fn stderr() -> bool {
+    enabled(io::stderr().is_terminal())
+}
+
+/// `text` in the SGR `style` when `on`, else as is.
+pub fn paint(text: &str, style: &str, on: bool) -> String {
+    if on {
+        format!("\x1b[{}m{}\x1b[0m", style, text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Print `message` to stderr after a `Warning:`, in yellow if stderr may be styled.
+pub fn warn(message: impl Display) {
+    eprintln!("{} {}", paint("Warning:", YELLOW, stderr()), message);
+}
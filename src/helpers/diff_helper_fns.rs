@@ -0,0 +1,80 @@
+/// One step of turning `a` into `b`, by index.
+pub enum Step {
+    Same(usize, usize),
+    Removed(usize),
+    Added(usize),
+}
+
+/// The shortest edit script from `a` to `b` (Myers' algorithm), with `same` saying which items
+/// match. Memory grows with the number of edits rather than with `a.len() * b.len()`.
+pub fn diff<A, B>(a: &[A], b: &[B], same: impl Fn(&A, &B) -> bool) -> Vec<Step> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    // furthest x reached on each diagonal k = x - y, offset so k can index from -max
+    let at = |k: isize| (k + max) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = vec![];
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && same(&a[x as usize], &b[y as usize]) {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut steps = vec![];
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            steps.push(Step::Same(x as usize, y as usize));
+        }
+        if d > 0 {
+            if x == prev_x {
+                steps.push(Step::Added(prev_y as usize));
+            } else {
+                steps.push(Step::Removed(prev_x as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    steps.reverse();
+    steps
+}
+
+/// Lines of `old` and `new` prefixed with `-`, `+` or a space.
+pub fn line_diff(old: &str, new: &str) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    diff(&a, &b, |x, y| x == y)
+        .into_iter()
+        .map(|step| match step {
+            Step::Same(i, _) => format!(" {}", a[i]),
+            Step::Removed(i) => format!("-{}", a[i]),
+            Step::Added(j) => format!("+{}", b[j]),
+        })
+        .collect()
+}
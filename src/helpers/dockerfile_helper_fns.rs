@@ -0,0 +1,197 @@
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::diff_helper_fns as diff;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Build file names podman understands, in the order `podman build` itself prefers them.
+const BUILDFILE_NAMES: [&str; 2] = ["Containerfile", "Dockerfile"];
+
+/// Build files in `dir`: `Containerfile` and `Dockerfile` first, then any `Containerfile.<name>` / `Dockerfile.<name>`
+/// variants sorted by name.
+pub fn buildfile_candidates(dir: &Path) -> Vec<PathBuf> {
+    let mut plain = vec![];
+    let mut suffixed = vec![];
+    if let Ok(entries) = fs::read_dir(dir) {
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if BUILDFILE_NAMES.contains(&file_name.as_str()) {
+                plain.push(path);
+            } else if is_buildfile_name(&file_name) {
+                suffixed.push(path);
+            }
+        }
+    }
+    plain.sort_by_key(|p| {
+        BUILDFILE_NAMES
+            .iter()
+            .position(|n| p.file_name().map(|f| f == *n).unwrap_or(false))
+    });
+    suffixed.sort();
+    plain.extend(suffixed);
+    plain
+}
+
+/// `Containerfile`, `Dockerfile`, or a `Containerfile.<name>` / `Dockerfile.<name>` variant.
+pub fn is_buildfile_name(file_name: &str) -> bool {
+    BUILDFILE_NAMES
+        .iter()
+        .any(|n| file_name == *n || file_name.starts_with(&format!("{}.", n)))
+}
+
+/// The build file to use for the compose file / dir at `dir`, if there is one.
+pub fn find_buildfile(dir: &Path) -> Option<PathBuf> {
+    buildfile_candidates(dir).into_iter().next()
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct DockerfileInstruction {
+    pub keyword: String,
+    pub args: String,
+    pub line: usize,
+}
+
+/// Split a Dockerfile into its instructions, joining `\` continuation lines and dropping comments.
+pub fn read_instructions(dockerfile: &Path) -> Result<Vec<DockerfileInstruction>, String> {
+    let content = fs::read_to_string(dockerfile)
+        .map_err(|e| format!("Failed to read '{}': {}", dockerfile.display(), e))?;
+    Ok(parse_instructions(&content))
+}
+
+/// The build file's lines numbered, with instruction keywords in bold and comments dimmed when `color` is set.
+pub fn highlighted_lines(content: &str, color: bool) -> Vec<String> {
+    let starts: Vec<usize> = parse_instructions(content).iter().map(|i| i.line).collect();
+    let width = content.lines().count().to_string().len();
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let trimmed = line.trim_start();
+            let indent = &line[..line.len() - trimmed.len()];
+            let text = if !color {
+                line.to_string()
+            } else if trimmed.starts_with('#') {
+                color::paint(line, color::DIM, true)
+            } else if starts.contains(&(i + 1)) {
+                let keyword_len = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+                format!(
+                    "{}{}{}",
+                    indent,
+                    color::paint(&trimmed[..keyword_len], color::BOLD, true),
+                    &trimmed[keyword_len..]
+                )
+            } else {
+                line.to_string()
+            };
+            format!("{:>width$}: {}", i + 1, text, width = width)
+        })
+        .collect()
+}
+
+pub fn parse_instructions(content: &str) -> Vec<DockerfileInstruction> {
+    let mut instructions = vec![];
+    let mut current = String::new();
+    let mut start_line = 0;
+
+    for (i, line) in content.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') || (trimmed.is_empty() && current.is_empty()) {
+            continue;
+        }
+        if current.is_empty() {
+            start_line = i + 1;
+        }
+        if let Some(stripped) = trimmed.strip_suffix('\\') {
+            current.push_str(stripped.trim_end());
+            current.push(' ');
+            continue;
+        }
+        current.push_str(trimmed);
+
+        let joined = current.trim().to_string();
+        current.clear();
+        if joined.is_empty() {
+            continue;
+        }
+        let (keyword, args) = match joined.split_once(char::is_whitespace) {
+            Some((k, a)) => (k.to_uppercase(), a.trim().to_string()),
+            None => (joined.to_uppercase(), String::new()),
+        };
+        instructions.push(DockerfileInstruction {
+            keyword,
+            args,
+            line: start_line,
+        });
+    }
+    instructions
+}
+
+/// Strip the shell wrapper podman/buildah put around history `CreatedBy` entries,
+/// e.g. `|1 USERNAME=me /bin/sh -c #(nop) COPY file:abc in /app`.
+pub fn normalize_created_by(created_by: &str) -> String {
+    let mut s = created_by.trim();
+    if s.starts_with('|') {
+        // |N ARG=val ... prefix lists the build args in effect
+        if let Some(idx) = s.find("/bin/sh -c") {
+            s = &s[idx..];
+        }
+    }
+    s = s.trim_start_matches("/bin/sh -c").trim();
+    s = s.trim_start_matches("#(nop)").trim();
+    collapse_whitespace(s)
+}
+
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// One row of the side-by-side diff. `history` is None when no history entry matched the instruction.
+pub struct DiffRow {
+    pub instruction: DockerfileInstruction,
+    pub history: Option<String>,
+}
+
+/// Match the instructions of the final build stage against the image's history (oldest first), in
+/// order. RUN instructions must match their command exactly; other instructions match on keyword
+/// and their last argument, since history records COPY/ADD sources as content hashes.
+pub fn diff_against_history(
+    instructions: &[DockerfileInstruction],
+    history: &[String],
+) -> Vec<DiffRow> {
+    let last_from = instructions
+        .iter()
+        .rposition(|i| i.keyword == "FROM")
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let instructions = &instructions[last_from..];
+    let normalized: Vec<String> = history.iter().map(|h| normalize_created_by(h)).collect();
+
+    let matches = |instruction: &DockerfileInstruction, h: &String| {
+        let args = collapse_whitespace(&instruction.args);
+        if instruction.keyword == "RUN" {
+            h == &args || h == &format!("RUN {}", args) || h.ends_with(&args)
+        } else {
+            let last_arg = args.split_whitespace().last().unwrap_or("");
+            h.starts_with(&instruction.keyword) && h.ends_with(last_arg)
+        }
+    };
+    let mut matched: Vec<Option<String>> = vec![None; instructions.len()];
+    for step in diff::diff(instructions, &normalized, matches) {
+        if let diff::Step::Same(i, j) = step {
+            matched[i] = Some(normalized[j].clone());
+        }
+    }
+
+    instructions
+        .iter()
+        .zip(matched)
+        .map(|(instruction, history)| DiffRow {
+            instruction: instruction.clone(),
+            history,
+        })
+        .collect()
+}
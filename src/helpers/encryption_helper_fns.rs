@@ -0,0 +1,50 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::path::Path;
+
+/// Recorded as `encryption` in the output json of encrypted entries.
+pub const ALGORITHM: &str = "chacha20poly1305";
+
+/// The key file holds 32 random bytes, base64 encoded, e.g. from `head -c 32 /dev/urandom | base64`.
+pub fn read_key(key_file: &Path) -> Result<ChaCha20Poly1305, String> {
+    let content = fs::read_to_string(key_file)
+        .map_err(|e| format!("Failed to read '{}': {}", key_file.display(), e))?;
+    let key = STANDARD
+        .decode(content.trim())
+        .map_err(|e| format!("'{}' is not base64: {}", key_file.display(), e))?;
+    if key.len() != 32 {
+        return Err(format!(
+            "'{}' holds a {} byte key, expected 32",
+            key_file.display(),
+            key.len()
+        ));
+    }
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key)))
+}
+
+/// Returns the base64 ciphertext and the base64 nonce it was encrypted with.
+pub fn encrypt(cipher: &ChaCha20Poly1305, plaintext: &str) -> Result<(String, String), String> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_bytes())
+        .map_err(|e| format!("Failed to encrypt: {}", e))?;
+    Ok((STANDARD.encode(ciphertext), STANDARD.encode(nonce)))
+}
+
+pub fn decrypt(cipher: &ChaCha20Poly1305, ciphertext: &str, nonce: &str) -> Result<String, String> {
+    let ciphertext = STANDARD
+        .decode(ciphertext)
+        .map_err(|e| format!("Ciphertext is not base64: {}", e))?;
+    let nonce = STANDARD
+        .decode(nonce)
+        .map_err(|e| format!("Nonce is not base64: {}", e))?;
+    if nonce.len() != 12 {
+        return Err(format!("Nonce is {} bytes, expected 12", nonce.len()));
+    }
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+        .map_err(|_| "Failed to decrypt, wrong key or tampered secret".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("Decrypted secret is not utf-8: {}", e))
+}
@@ -0,0 +1,166 @@
+use crate::args::{BuildEngine, PodmanScope};
+use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::image_name_helper_fns as image_name;
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+use std::sync::OnceLock;
+
+static ENGINE: OnceLock<BuildEngine> = OnceLock::new();
+static SCOPE: OnceLock<PodmanScope> = OnceLock::new();
+
+/// Binaries that work on podman's storage, so run under sudo for --podman-scope system.
+const SCOPED_BINARIES: [&str; 3] = ["podman", "buildah", "podman-compose"];
+
+/// Auto-detection order when --build-engine isn't given.
+const DETECT_ORDER: [BuildEngine; 3] = [
+    BuildEngine::Podman,
+    BuildEngine::Buildah,
+    BuildEngine::Docker,
+];
+
+/// Settle which engine pulls and builds for this run: `choice` if given, else the first of podman,
+/// buildah and docker found on PATH. Falls back to podman so a missing binary fails loudly later.
+pub fn init(choice: Option<BuildEngine>) -> BuildEngine {
+    *ENGINE.get_or_init(|| {
+        choice.unwrap_or_else(|| {
+            DETECT_ORDER
+                .into_iter()
+                .find(|engine| on_path(engine.binary()))
+                .unwrap_or(BuildEngine::Podman)
+        })
+    })
+}
+
+/// The engine settled by `init`, podman if it wasn't called.
+pub fn engine() -> BuildEngine {
+    ENGINE.get().copied().unwrap_or(BuildEngine::Podman)
+}
+
+pub fn set_scope(scope: PodmanScope) {
+    let _ = SCOPE.set(scope);
+}
+
+/// The --podman-scope in effect, user if `set_scope` wasn't called.
+pub fn scope() -> PodmanScope {
+    SCOPE.get().copied().unwrap_or(PodmanScope::User)
+}
+
+/// A command running `binary`, through sudo when it works on podman's storage and the scope is system.
+pub fn command(binary: &str) -> Command {
+    if SCOPED_BINARIES.contains(&binary) {
+        privileged(binary)
+    } else {
+        Command::new(binary)
+    }
+}
+
+/// A command running `binary` with access to the scope's storage: through sudo for system.
+pub fn privileged(binary: &str) -> Command {
+    match scope() {
+        PodmanScope::System => {
+            let mut cmd = Command::new("sudo");
+            cmd.arg(binary);
+            cmd
+        }
+        PodmanScope::User => Command::new(binary),
+    }
+}
+
+/// Arguments to build `image` from `buildfile`. podman and buildah default the context to the
+/// current dir; docker needs it spelled out. `cache_ref` is a registry ref to pull and push layer
+/// cache from; docker only does that through buildx, so it's left out there. `platform`, e.g.
+/// `linux/arm64`, builds for another arch, emulated if need be.
+pub fn build_cmd_args<'a>(
+    image: &'a str,
+    buildfile: &'a str,
+    build_args: &[&'a str],
+    labels: &[&'a str],
+    cache_ref: Option<&'a str>,
+    no_cache: bool,
+    platform: Option<&'a str>,
+) -> Vec<&'a str> {
+    let mut x = vec!["build", "-t", image, "-f", buildfile];
+    if let Some(platform) = platform {
+        x.extend(["--platform", platform]);
+    }
+    if no_cache {
+        x.push("--no-cache");
+    }
+    for arg in build_args {
+        x.push("--build-arg");
+        x.push(arg);
+    }
+    for label in labels {
+        x.push("--label");
+        x.push(label);
+    }
+    match cache_ref {
+        Some(_) if engine() == BuildEngine::Docker => {
+            color::warn("--build-cache needs podman or buildah, building without it.")
+        }
+        Some(cache_ref) => x.extend([
+            "--layers",
+            "--cache-from",
+            cache_ref,
+            "--cache-to",
+            cache_ref,
+        ]),
+        None => {}
+    }
+    if engine() == BuildEngine::Docker {
+        x.push(".");
+    }
+    x
+}
+
+/// The name one arch of a multi-arch `image` is built as: the platform added to the tag, so
+/// `localhost/foo:1.2` for `linux/arm64` is `localhost/foo:1.2-linux-arm64`.
+pub fn platform_image(image: &str, platform: &str) -> String {
+    let repository = image_name::repository_of(image);
+    let tag = image[repository.len()..].trim_start_matches(':');
+    let tag = if tag.is_empty() { "latest" } else { tag };
+    format!("{}:{}-{}", repository, tag, platform.replace('/', "-"))
+}
+
+/// Make `image` a manifest list of the already built `arch_images`, replacing whatever had the
+/// name before: an earlier manifest list, or a single-arch image from a build without --platforms.
+pub fn assemble_manifest(image: &str, arch_images: &[String]) -> Result<(), String> {
+    let binary = engine().binary();
+    let _ = command(binary).args(["manifest", "rm", image]).output();
+    let _ = command(binary).args(["rmi", image]).output();
+    if !cmd::exec_cmd(binary, vec!["manifest", "create", image]).success() {
+        return Err(format!("couldn't create the manifest list {}", image));
+    }
+    for arch_image in arch_images {
+        let source = format!("containers-storage:{}", arch_image);
+        if !cmd::exec_cmd(binary, vec!["manifest", "add", image, &source]).success() {
+            return Err(format!("couldn't add {} to the manifest list", arch_image));
+        }
+    }
+    Ok(())
+}
+
+/// Push the manifest list `image` and every image in it to the registry its name points at.
+pub fn push_manifest(image: &str) -> Result<(), String> {
+    let destination = format!("docker://{}", image);
+    if cmd::exec_cmd(
+        engine().binary(),
+        vec!["manifest", "push", "--all", image, &destination],
+    )
+    .success()
+    {
+        Ok(())
+    } else {
+        Err(format!("couldn't push the manifest list {}", image))
+    }
+}
+
+/// Whether `binary` is a file in one of the dirs on PATH.
+pub fn on_path(binary: &str) -> bool {
+    env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).any(|dir| Path::new(&dir).join(binary).is_file()))
+        .unwrap_or(false)
+}
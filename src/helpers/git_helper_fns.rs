@@ -0,0 +1,62 @@
+use std::path::Path;
+use std::process::Command;
+
+pub const REVISION_LABEL: &str = "org.opencontainers.image.revision";
+pub const SOURCE_LABEL: &str = "org.opencontainers.image.source";
+
+/// `git <args>` run in `dir`, trimmed. None outside a repo, without git, or on empty output.
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    Command::new("git")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|out| !out.is_empty())
+}
+
+/// The branch checked out in `dir`; None on a detached HEAD.
+pub fn branch(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "--abbrev-ref", "HEAD"]).filter(|branch| branch != "HEAD")
+}
+
+/// The commit checked out in `dir`.
+pub fn revision(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// The URL of the `origin` remote, or else the first remote, with any user or token in it removed.
+pub fn remote_url(dir: &Path) -> Option<String> {
+    let remotes = git(dir, &["remote"])?;
+    let remote = remotes
+        .lines()
+        .find(|remote| *remote == "origin")
+        .or_else(|| remotes.lines().next())?;
+    git(dir, &["remote", "get-url", remote]).map(|url| strip_credentials(&url))
+}
+
+fn strip_credentials(url: &str) -> String {
+    match url.split_once("://") {
+        Some((scheme, rest)) => match rest.split_once('/') {
+            Some((host, path)) => match host.rsplit_once('@') {
+                Some((_, host)) => format!("{}://{}/{}", scheme, host, path),
+                None => url.to_string(),
+            },
+            None => url.to_string(),
+        },
+        None => url.to_string(),
+    }
+}
+
+/// `revision=<sha>` and `source=<remote>` OCI labels for a build in `dir`, for those git knows.
+pub fn provenance_labels(dir: &Path) -> Vec<String> {
+    let mut labels = vec![];
+    if let Some(revision) = revision(dir) {
+        labels.push(format!("{}={}", REVISION_LABEL, revision));
+    }
+    if let Some(url) = remote_url(dir) {
+        labels.push(format!("{}={}", SOURCE_LABEL, url));
+    }
+    labels
+}
@@ -0,0 +1,26 @@
+use serde_json::Value;
+use std::sync::OnceLock;
+
+static HOSTNAME: OnceLock<String> = OnceLock::new();
+
+/// This machine's hostname, looked up once; empty if it can't be read.
+pub fn this_host() -> &'static str {
+    HOSTNAME.get_or_init(|| {
+        hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_default()
+    })
+}
+
+/// Where an input json entry's file is on this host: its `host_overrides` path for this hostname
+/// (full, or the part before the first dot), else `filenm`. One input json can then serve hosts
+/// that keep the same file in different places.
+pub fn local_filenm(entry: &Value) -> Option<&str> {
+    let overrides = &entry["host_overrides"];
+    let host = this_host();
+    let short_host = host.split('.').next().unwrap_or(host);
+    overrides[host]
+        .as_str()
+        .or_else(|| overrides[short_host].as_str())
+        .or_else(|| entry["filenm"].as_str())
+}
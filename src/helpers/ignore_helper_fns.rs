@@ -0,0 +1,139 @@
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Version control dirs, never worth descending into.
+const VCS_DIRS: [&str; 3] = [".git", ".hg", ".svn"];
+
+/// One .gitignore line.
+struct Rule {
+    regex: Regex,
+    /// `!pattern`, re-includes what an earlier rule ignored
+    negated: bool,
+    /// `pattern/`, only matches dirs
+    dir_only: bool,
+    /// has a `/` before the end, so it's matched against the path relative to the .gitignore's dir
+    /// instead of just the name
+    anchored: bool,
+}
+
+/// The subset of .gitignore the scan needs: `*`, `**`, `?`, leading and trailing `/`, and `!`.
+/// Each dir's .gitignore is read once; files above the scan root are not consulted.
+pub struct IgnoreRules {
+    by_dir: RefCell<HashMap<PathBuf, Vec<Rule>>>,
+    ignored: RefCell<usize>,
+}
+
+impl IgnoreRules {
+    pub fn new() -> IgnoreRules {
+        IgnoreRules {
+            by_dir: RefCell::new(HashMap::new()),
+            ignored: RefCell::new(0),
+        }
+    }
+
+    /// Whether `path` under `root` is ignored. Callers prune ignored dirs, so the dirs above `path`
+    /// are known not to be ignored and only `path` itself is checked.
+    pub fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut ignored = is_dir && VCS_DIRS.contains(&name.as_str());
+
+        // rules from deeper dirs come later, so they win like in git
+        let mut dirs: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|d| d.starts_with(root))
+            .collect();
+        dirs.reverse();
+        for dir in dirs {
+            let relative = match path.strip_prefix(dir) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            let mut by_dir = self.by_dir.borrow_mut();
+            let rules = by_dir
+                .entry(dir.to_path_buf())
+                .or_insert_with(|| read_rules(dir));
+            for rule in rules.iter() {
+                if rule.dir_only && !is_dir {
+                    continue;
+                }
+                let subject = if rule.anchored { &relative } else { &name };
+                if rule.regex.is_match(subject) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        if ignored {
+            *self.ignored.borrow_mut() += 1;
+        }
+        ignored
+    }
+
+    /// How many files and dirs were skipped so far.
+    pub fn ignored_count(&self) -> usize {
+        *self.ignored.borrow()
+    }
+}
+
+fn read_rules(dir: &Path) -> Vec<Rule> {
+    let content = match fs::read_to_string(dir.join(".gitignore")) {
+        Ok(content) => content,
+        Err(_) => return vec![],
+    };
+    content.lines().filter_map(parse_rule).collect()
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let line = line.trim_end();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, pattern) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix('\\').unwrap_or(line)),
+    };
+    let (dir_only, pattern) = match pattern.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(Rule {
+        regex: Regex::new(&glob_to_regex(pattern)).ok()?,
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+/// `**/` matches any number of dirs, `*` and `?` don't cross a `/`.
+pub fn glob_to_regex(glob: &str) -> String {
+    let mut regex = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.next_if_eq(&'/').is_some() {
+                    regex.push_str("(?:.*/)?");
+                } else {
+                    regex.push_str(".*");
+                }
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    regex
+}
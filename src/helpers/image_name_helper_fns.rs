@@ -0,0 +1,31 @@
+/// `docker.io/library/nginx:1.27` and `...nginx@sha256:...` -> `docker.io/library/nginx`. A colon
+/// before the last `/` is a registry port, not a tag.
+pub fn repository_of(image: &str) -> &str {
+    let image = image.split_once('@').map_or(image, |(name, _)| name);
+    let last_slash = image.rfind('/').map_or(0, |i| i + 1);
+    match image[last_slash..].rfind(':') {
+        Some(colon) => &image[..last_slash + colon],
+        None => image,
+    }
+}
+
+/// `image` as the engines resolve it: `nginx`, `library/nginx:latest` and
+/// `docker.io/library/nginx` are all `docker.io/library/nginx:latest`. A first part with a `.` or
+/// `:` in it, or `localhost`, is a registry; anything else is on docker.io.
+pub fn qualified_name(image: &str) -> String {
+    let mut name = match image.split_once('/') {
+        Some((registry, _)) if registry.contains(['.', ':']) || registry == "localhost" => {
+            image.to_string()
+        }
+        _ => format!("docker.io/{}", image),
+    };
+    if let Some(path) = name.strip_prefix("docker.io/") {
+        if !path.contains('/') {
+            name = format!("docker.io/library/{}", path);
+        }
+    }
+    if !name.contains('@') && repository_of(&name) == name {
+        name.push_str(":latest");
+    }
+    name
+}
@@ -0,0 +1,190 @@
+use chrono::Local;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+const IDENTIFIER: &str = "podman-compose-mgr";
+const SYSLOG_SOCKET: &str = "/dev/log";
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Syslog severities: command stdout is info, its stderr err.
+const INFO: u8 = 6;
+const ERR: u8 = 3;
+
+/// What the mirrored line came from.
+struct LineContext {
+    image: String,
+    action: String,
+}
+
+/// Somewhere to mirror command output to as it happens.
+trait LogSink: Send + Sync {
+    fn write_line(
+        &self,
+        context: Option<&LineContext>,
+        severity: u8,
+        line: &str,
+    ) -> Result<(), String>;
+}
+
+struct FileSink {
+    file: Mutex<File>,
+}
+
+impl LogSink for FileSink {
+    fn write_line(
+        &self,
+        context: Option<&LineContext>,
+        severity: u8,
+        line: &str,
+    ) -> Result<(), String> {
+        let mut prefix = match context {
+            Some(c) => format!("[{} {}] ", c.action, c.image),
+            None => String::new(),
+        };
+        if severity == ERR {
+            prefix.push_str("error: ");
+        }
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{} {}{}", Local::now().to_rfc3339(), prefix, line)
+            .map_err(|e| e.to_string())
+    }
+}
+
+struct SyslogSink {
+    socket: UnixDatagram,
+}
+
+impl LogSink for SyslogSink {
+    fn write_line(
+        &self,
+        context: Option<&LineContext>,
+        severity: u8,
+        line: &str,
+    ) -> Result<(), String> {
+        let prefix = match context {
+            Some(c) => format!("[{} {}] ", c.action, c.image),
+            None => String::new(),
+        };
+        // facility user (1)
+        let msg = format!(
+            "<{}>{} {}[{}]: {}{}",
+            8 + severity,
+            Local::now().format("%b %e %H:%M:%S"),
+            IDENTIFIER,
+            std::process::id(),
+            prefix,
+            line
+        );
+        self.socket
+            .send(msg.as_bytes())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+struct JournaldSink {
+    socket: UnixDatagram,
+}
+
+impl LogSink for JournaldSink {
+    fn write_line(
+        &self,
+        context: Option<&LineContext>,
+        severity: u8,
+        line: &str,
+    ) -> Result<(), String> {
+        // journald's native protocol, one FIELD=value per line; lines never contain a newline here
+        let mut msg = format!(
+            "MESSAGE={}\nSYSLOG_IDENTIFIER={}\nPRIORITY={}\n",
+            line, IDENTIFIER, severity
+        );
+        if let Some(c) = context {
+            msg.push_str(&format!("PCM_IMAGE={}\nPCM_ACTION={}\n", c.image, c.action));
+        }
+        self.socket
+            .send(msg.as_bytes())
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+static SINK: OnceLock<Box<dyn LogSink>> = OnceLock::new();
+static CONTEXT: Mutex<Option<LineContext>> = Mutex::new(None);
+
+/// Validate a --log-sink value: `journald`, `syslog` or `file:<path>`.
+pub fn check_log_sink(spec: &str) -> Result<String, String> {
+    match spec {
+        "journald" | "syslog" => Ok(spec.to_string()),
+        _ => match spec.strip_prefix("file:") {
+            Some(path) if !path.is_empty() => Ok(spec.to_string()),
+            _ => Err(format!("'{}' isn't journald, syslog or file:<path>", spec)),
+        },
+    }
+}
+
+/// Open the sink named by --log-sink; output is mirrored to it from then on.
+pub fn init(spec: &str) -> Result<(), String> {
+    let connect = |path: &str| {
+        let socket =
+            UnixDatagram::unbound().map_err(|e| format!("Failed to create socket: {}", e))?;
+        socket
+            .connect(path)
+            .map_err(|e| format!("Failed to connect to {}: {}", path, e))?;
+        Ok::<UnixDatagram, String>(socket)
+    };
+    let sink: Box<dyn LogSink> = match spec {
+        "journald" => Box::new(JournaldSink {
+            socket: connect(JOURNALD_SOCKET)?,
+        }),
+        "syslog" => Box::new(SyslogSink {
+            socket: connect(SYSLOG_SOCKET)?,
+        }),
+        _ => {
+            let path = PathBuf::from(spec.trim_start_matches("file:"));
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .map_err(|e| format!("Failed to open '{}': {}", path.display(), e))?;
+            Box::new(FileSink {
+                file: Mutex::new(file),
+            })
+        }
+    };
+    let _ = SINK.set(sink);
+    Ok(())
+}
+
+/// Tag the lines mirrored from now on with the image and what's being done to it.
+pub fn set_context(image: &str, action: &str) {
+    *CONTEXT.lock().unwrap() = Some(LineContext {
+        image: image.to_string(),
+        action: action.to_string(),
+    });
+}
+
+pub fn clear_context() {
+    *CONTEXT.lock().unwrap() = None;
+}
+
+/// Send a line of command output to the sink, if --log-sink gave one. Failures are reported, not fatal.
+pub fn mirror(line: &str) {
+    mirror_at(INFO, line);
+}
+
+/// `mirror` for a line the command wrote to stderr.
+pub fn mirror_error(line: &str) {
+    mirror_at(ERR, line);
+}
+
+fn mirror_at(severity: u8, line: &str) {
+    if let Some(sink) = SINK.get() {
+        let context = CONTEXT.lock().unwrap();
+        if let Err(e) = sink.write_line(context.as_ref(), severity, line) {
+            eprintln!("Error writing to log sink: {}", e);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::args::Args;
+
+use std::fs;
+use std::path::PathBuf;
+
+/// Where a file we name ourselves goes: `<output dir>/<subdir>/<stem>.<extension>` with --output-dir
+/// (or the project's `output_dir`), else the current dir. If that file exists, `-1`, `-2`... is
+/// added to the stem rather than overwriting it.
+pub fn default_output_path(
+    args: &Args,
+    subdir: &str,
+    stem: &str,
+    extension: &str,
+) -> Result<PathBuf, String> {
+    let dir = match &args.output_dir {
+        Some(output_dir) => {
+            let dir = output_dir.join(subdir);
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+            dir
+        }
+        None => PathBuf::new(),
+    };
+    let mut path = dir.join(format!("{}.{}", stem, extension));
+    let mut n = 1;
+    while path.exists() {
+        path = dir.join(format!("{}-{}.{}", stem, n, extension));
+        n += 1;
+    }
+    Ok(path)
+}
@@ -1,10 +1,43 @@
-use std::process::Command;
+use crate::args::PodmanScope;
+use crate::helpers::engine_helper_fns;
+
 //use dateparser::parse;
 use chrono::{DateTime, Local, TimeZone, Utc};
 use regex::Regex;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+static ROOTLESS: OnceLock<Result<bool, String>> = OnceLock::new();
+
+/// Whether podman runs rootless for this user, asked once per run.
+pub fn is_rootless() -> Result<bool, String> {
+    ROOTLESS
+        .get_or_init(|| {
+            let output = engine_helper_fns::command("podman")
+                .args(["info", "--format", "{{.Host.Security.Rootless}}"])
+                .output()
+                .map_err(|e| format!("Failed to execute podman: {}", e))?;
+            if !output.status.success() {
+                return Err(format!(
+                    "podman info failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).trim() == "true")
+        })
+        .clone()
+}
+
+/// Lowest port an unprivileged user may bind, 1024 unless the sysctl was lowered.
+pub fn unprivileged_port_start() -> u16 {
+    std::fs::read_to_string("/proc/sys/net/ipv4/ip_unprivileged_port_start")
+        .ok()
+        .and_then(|s| s.trim().parse().ok())
+        .unwrap_or(1024)
+}
 
 pub fn get_podman_image_upstream_create_time(img: &str) -> Result<DateTime<Local>, String> {
-    let mut cmd = Command::new("podman");
+    let mut cmd = engine_helper_fns::command("podman");
     cmd.arg("image");
     cmd.arg("inspect");
     cmd.arg("--format");
@@ -34,7 +67,7 @@ pub fn get_podman_image_upstream_create_time(img: &str) -> Result<DateTime<Local
 }
 
 pub fn get_podman_ondisk_modify_time(img: &str) -> Result<DateTime<Local>, String> {
-    let mut cmd = Command::new("podman");
+    let mut cmd = engine_helper_fns::command("podman");
     cmd.arg("image");
     cmd.arg("inspect");
     cmd.arg("--format");
@@ -50,12 +83,19 @@ pub fn get_podman_ondisk_modify_time(img: &str) -> Result<DateTime<Local>, Strin
         // Ok(x?)
         let id = stdout.trim().to_string();
 
-        let homedir = std::env::var("HOME").unwrap();
-        let path = format!(
-            "{}/.local/share/containers/storage/overlay-images/{}/manifest",
-            homedir, id
-        );
-        let mut cmd2 = Command::new("stat");
+        let path = match engine_helper_fns::scope() {
+            PodmanScope::System => {
+                format!("/var/lib/containers/storage/overlay-images/{}/manifest", id)
+            }
+            PodmanScope::User => {
+                let homedir = std::env::var("HOME").unwrap();
+                format!(
+                    "{}/.local/share/containers/storage/overlay-images/{}/manifest",
+                    homedir, id
+                )
+            }
+        };
+        let mut cmd2 = engine_helper_fns::privileged("stat");
         cmd2.arg("-c");
         cmd2.arg("%y");
         cmd2.arg(path);
@@ -142,3 +182,221 @@ fn convert_str_to_date(date_str: &str) -> Result<DateTime<Local>, String> {
         }
     }
 }
+
+/// Runs `podman image prune -f` and returns how many images were removed and the bytes reclaimed.
+pub fn prune_dangling_images() -> Result<(usize, u64), String> {
+    // grab sizes of the dangling images first, prune only tells us the ids it removed
+    let mut cmd = engine_helper_fns::command("podman");
+    cmd.arg("images");
+    cmd.arg("--filter");
+    cmd.arg("dangling=true");
+    cmd.arg("--noheading");
+    cmd.arg("--no-trunc");
+    cmd.arg("--format");
+    cmd.arg("{{.ID}}");
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        return Err(format!("podman failed: {}", stderr));
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+    let mut sizes: Vec<(String, u64)> = vec![];
+    for id in stdout.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        let size = get_podman_image_size(id).unwrap_or(0);
+        sizes.push((id.trim_start_matches("sha256:").to_string(), size));
+    }
+
+    let mut cmd2 = engine_helper_fns::command("podman");
+    cmd2.arg("image");
+    cmd2.arg("prune");
+    cmd2.arg("-f");
+    let output2 = cmd2
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output2.status.success() {
+        let stderr = String::from_utf8(output2.stderr)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        return Err(format!("podman failed: {}", stderr));
+    }
+    let stdout2 = String::from_utf8(output2.stdout)
+        .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+
+    let mut removed = 0;
+    let mut reclaimed = 0;
+    for id in stdout2.lines().map(|l| l.trim()).filter(|l| !l.is_empty()) {
+        removed += 1;
+        if let Some((_, size)) = sizes
+            .iter()
+            .find(|(x, _)| x.starts_with(id) || id.starts_with(x.as_str()))
+        {
+            reclaimed += size;
+        }
+    }
+    Ok((removed, reclaimed))
+}
+
+pub fn get_podman_image_size(img: &str) -> Result<u64, String> {
+    let mut cmd = engine_helper_fns::command("podman");
+    cmd.arg("image");
+    cmd.arg("inspect");
+    cmd.arg("--format");
+    cmd.arg("{{.Size}}");
+    cmd.arg(img);
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if output.status.success() {
+        let stdout = String::from_utf8(output.stdout)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        stdout
+            .trim()
+            .parse::<u64>()
+            .map_err(|e| format!("Failed to parse image size '{}': {}", stdout.trim(), e))
+    } else {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        Err(format!("podman failed: {}", stderr))
+    }
+}
+
+/// The image's labels; empty when it has none.
+pub fn get_podman_image_labels(img: &str) -> Result<HashMap<String, String>, String> {
+    let output = engine_helper_fns::command("podman")
+        .args(["image", "inspect", "--format", "{{json .Labels}}", img])
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "podman image inspect failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    // `null` for an image without labels
+    let labels: Option<HashMap<String, String>> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse podman labels json: {}", e))?;
+    Ok(labels.unwrap_or_default())
+}
+
+/// Returns the `CreatedBy` of each layer in the image's history, oldest first.
+pub fn get_podman_image_history(img: &str) -> Result<Vec<String>, String> {
+    Ok(get_podman_image_layers(img)?
+        .into_iter()
+        .map(|layer| layer.created_by)
+        .collect())
+}
+
+/// One entry of `podman history`.
+pub struct ImageLayer {
+    pub created: Option<DateTime<Local>>,
+    pub created_by: String,
+    pub size: u64,
+}
+
+/// The image's history, oldest first.
+pub fn get_podman_image_layers(img: &str) -> Result<Vec<ImageLayer>, String> {
+    let mut cmd = engine_helper_fns::command("podman");
+    cmd.arg("history");
+    cmd.arg("--no-trunc");
+    cmd.arg("--format");
+    cmd.arg("json");
+    cmd.arg(img);
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        return Err(format!("podman failed: {}", stderr));
+    }
+    let layers: Vec<serde_json::Value> = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse podman history json: {}", e))?;
+    let mut history: Vec<ImageLayer> = layers
+        .iter()
+        .map(|l| ImageLayer {
+            created: l
+                .get("created")
+                .and_then(|c| c.as_str())
+                .and_then(|c| DateTime::parse_from_rfc3339(c).ok())
+                .map(|c| c.with_timezone(&Local)),
+            created_by: l
+                .get("CreatedBy")
+                .or_else(|| l.get("createdBy"))
+                .and_then(|c| c.as_str())
+                .unwrap_or("")
+                .to_string(),
+            size: l.get("size").and_then(|s| s.as_u64()).unwrap_or(0),
+        })
+        .collect();
+    history.reverse();
+    Ok(history)
+}
+
+/// The container's health: `starting`, `healthy` or `unhealthy`.
+pub fn health_status(container: &str) -> Result<String, String> {
+    let output = engine_helper_fns::command("podman")
+        .args(["inspect", "--format", "{{.State.Health.Status}}", container])
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "podman inspect failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Every named image in local storage (docker's with --build-engine docker) as `repository:tag`, sorted.
+/// Dangling `<none>` ones are left out.
+pub fn local_image_names() -> Result<Vec<String>, String> {
+    let binary = engine_helper_fns::engine().store_binary();
+    let output = engine_helper_fns::command(binary)
+        .args(["images", "--format", "{{.Repository}}:{{.Tag}}"])
+        .output()
+        .map_err(|e| format!("Failed to execute {}: {}", binary, e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} images failed: {}",
+            binary,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let mut names: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty() && !l.contains("<none>"))
+        .collect();
+    names.sort();
+    names.dedup();
+    Ok(names)
+}
+
+/// Containers created from `image`, running or not, as `name (state)`.
+pub fn containers_using(image: &str) -> Result<Vec<String>, String> {
+    let mut cmd = engine_helper_fns::command("podman");
+    cmd.arg("ps");
+    cmd.arg("-a");
+    cmd.arg("--filter");
+    cmd.arg(format!("ancestor={}", image));
+    cmd.arg("--format");
+    cmd.arg("{{.Names}} ({{.State}})");
+    let output = cmd
+        .output()
+        .map_err(|e| format!("Failed to execute podman: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8(output.stderr)
+            .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+        return Err(format!("podman failed: {}", stderr));
+    }
+    let stdout = String::from_utf8(output.stdout)
+        .map_err(|e| format!("Failed to parse podman output: {}", e))?;
+    Ok(stdout
+        .lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect())
+}
@@ -0,0 +1,57 @@
+use crate::compose;
+use crate::helpers::host_helper_fns as host;
+
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Shorter values would mask ordinary words in build output.
+const MIN_REDACTED_LEN: usize = 6;
+const MASK: &str = "********";
+
+static REDACTIONS: OnceLock<Vec<String>> = OnceLock::new();
+
+/// Collect what must never show up in output from the secrets inventory (the secret-mode input json):
+/// the md5 recorded for each entry, and the values in each entry's env file.
+pub fn load_inventory(input_json: &Path) -> Result<Vec<String>, String> {
+    let content = fs::read_to_string(input_json)
+        .map_err(|e| format!("Failed to read '{}': {}", input_json.display(), e))?;
+    let entries: Vec<Value> = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse '{}': {}", input_json.display(), e))?;
+    let mut values = vec![];
+    for entry in &entries {
+        if let Some(md5) = entry["md5"].as_str() {
+            values.push(md5.to_string());
+        }
+        if let Some(filenm) = host::local_filenm(entry) {
+            values.extend(
+                compose::read_env_file(Path::new(filenm))
+                    .into_iter()
+                    .map(|(_, value)| value),
+            );
+        }
+    }
+    values.retain(|v| v.len() >= MIN_REDACTED_LEN);
+    // longest first, so a value containing another is masked whole; ties sorted so dedup sees them
+    values.sort_unstable_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    values.dedup();
+    Ok(values)
+}
+
+pub fn init(values: Vec<String>) {
+    let _ = REDACTIONS.set(values);
+}
+
+/// `line` with every inventory value masked. A no-op unless --redact-secrets set things up.
+pub fn redact(line: &str) -> String {
+    let mut result = line.to_string();
+    if let Some(values) = REDACTIONS.get() {
+        for value in values {
+            if result.contains(value.as_str()) {
+                result = result.replace(value.as_str(), MASK);
+            }
+        }
+    }
+    result
+}
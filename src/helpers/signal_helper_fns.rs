@@ -0,0 +1,63 @@
+use crate::exit_code;
+use crate::helpers::audit_helper_fns as audit;
+use crate::helpers::color_helper_fns as color;
+
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use tokio::signal::unix::{signal, SignalKind};
+
+/// pid of the podman (or other) command running right now, 0 if none.
+static CHILD_PID: AtomicU32 = AtomicU32::new(0);
+
+/// On SIGTERM or SIGHUP, e.g. from systemd stopping the unit, stop the running command, note it in
+/// the audit log and exit with 128 + the signal number. Ctrl+C keeps its default behavior.
+pub fn install(audit_log: Option<PathBuf>) {
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                color::warn(format!("not handling SIGTERM/SIGHUP: {}", e));
+                return;
+            }
+        };
+        let received = runtime.block_on(async {
+            let mut term = signal(SignalKind::terminate()).ok()?;
+            let mut hup = signal(SignalKind::hangup()).ok()?;
+            tokio::select! {
+                _ = term.recv() => Some(("SIGTERM", 15)),
+                _ = hup.recv() => Some(("SIGHUP", 1)),
+            }
+        });
+        let Some((name, number)) = received else {
+            color::warn("not handling SIGTERM/SIGHUP");
+            return;
+        };
+
+        // a prompt may be waiting mid-line
+        eprintln!();
+        eprintln!("Received {}, stopping.", name);
+        let pid = CHILD_PID.load(Ordering::SeqCst);
+        if pid != 0 {
+            let _ = Command::new("kill")
+                .args(["-TERM", &pid.to_string()])
+                .status();
+        }
+        if let Some(audit_log) = &audit_log {
+            audit::record_to(audit_log, "interrupted", name, "stopped");
+        }
+        std::process::exit(exit_code::SIGNAL_BASE + number);
+    });
+}
+
+pub fn set_child(pid: u32) {
+    CHILD_PID.store(pid, Ordering::SeqCst);
+}
+
+pub fn clear_child() {
+    CHILD_PID.store(0, Ordering::SeqCst);
+}
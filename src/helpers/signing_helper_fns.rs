@@ -0,0 +1,60 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::fs;
+use std::path::Path;
+
+/// 32 base64 encoded bytes from `key_file`, the format both kinds of key file use.
+fn read_key_bytes(key_file: &Path) -> Result<[u8; 32], String> {
+    let content = fs::read_to_string(key_file)
+        .map_err(|e| format!("Failed to read '{}': {}", key_file.display(), e))?;
+    let key = STANDARD
+        .decode(content.trim())
+        .map_err(|e| format!("'{}' is not base64: {}", key_file.display(), e))?;
+    key.as_slice().try_into().map_err(|_| {
+        format!(
+            "'{}' holds a {} byte key, expected 32",
+            key_file.display(),
+            key.len()
+        )
+    })
+}
+
+/// The signing key file holds a 32 byte random seed, base64 encoded, e.g. from `head -c 32 /dev/urandom | base64`.
+pub fn read_signing_key(key_file: &Path) -> Result<SigningKey, String> {
+    Ok(SigningKey::from_bytes(&read_key_bytes(key_file)?))
+}
+
+/// The verify key file holds the public key, base64 encoded, as `public_key` prints it.
+pub fn read_verifying_key(key_file: &Path) -> Result<VerifyingKey, String> {
+    VerifyingKey::from_bytes(&read_key_bytes(key_file)?).map_err(|e| {
+        format!(
+            "'{}' is not an Ed25519 public key: {}",
+            key_file.display(),
+            e
+        )
+    })
+}
+
+pub fn public_key(key: &SigningKey) -> String {
+    STANDARD.encode(key.verifying_key().to_bytes())
+}
+
+/// The name is signed along with the value, so a signature can't vouch for another secret's value.
+fn message(name: &str, value: &str) -> Vec<u8> {
+    format!("{}\n{}", name, value).into_bytes()
+}
+
+/// Returns the base64 signature of the secret `name` holding `value`.
+pub fn sign(key: &SigningKey, name: &str, value: &str) -> String {
+    STANDARD.encode(key.sign(&message(name, value)).to_bytes())
+}
+
+pub fn verify(key: &VerifyingKey, name: &str, value: &str, signature: &str) -> Result<(), String> {
+    let signature = STANDARD
+        .decode(signature)
+        .map_err(|e| format!("Signature is not base64: {}", e))?;
+    let signature = Signature::from_slice(&signature)
+        .map_err(|_| format!("Signature is {} bytes, expected 64", signature.len()))?;
+    key.verify(&message(name, value), &signature)
+        .map_err(|_| "signature doesn't match".to_string())
+}
@@ -0,0 +1,94 @@
+use crate::state::StateDir;
+
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const HISTORY_FILE: &str = "images.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    /// "pull" or "build"
+    pub action: String,
+    /// unix seconds
+    pub ts: i64,
+    pub ok: bool,
+    pub duration_secs: u64,
+}
+
+impl HistoryEntry {
+    pub fn time(&self) -> DateTime<Local> {
+        Local.timestamp_opt(self.ts, 0).single().unwrap_or_default()
+    }
+
+    /// e.g. `ok, 42s`
+    pub fn outcome(&self) -> String {
+        format!(
+            "{}, {}s",
+            if self.ok { "ok" } else { "failed" },
+            self.duration_secs
+        )
+    }
+}
+
+/// The last pull or build of each image, kept in the state dir so it carries across runs.
+pub struct BuildHistory {
+    file: PathBuf,
+    entries: HashMap<String, HistoryEntry>,
+}
+
+impl BuildHistory {
+    pub fn load(state: &StateDir) -> Result<BuildHistory, String> {
+        let file = state.path().join("history").join(HISTORY_FILE);
+        let entries = if file.exists() {
+            let content = fs::read_to_string(&file)
+                .map_err(|e| format!("Failed to read '{}': {}", file.display(), e))?;
+            serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", file.display(), e))?
+        } else {
+            HashMap::new()
+        };
+        Ok(BuildHistory { file, entries })
+    }
+
+    /// Re-read the file, to see what another run recorded since we loaded it.
+    pub fn reload(&mut self) -> Result<(), String> {
+        if self.file.exists() {
+            let content = fs::read_to_string(&self.file)
+                .map_err(|e| format!("Failed to read '{}': {}", self.file.display(), e))?;
+            self.entries = serde_json::from_str(&content)
+                .map_err(|e| format!("Failed to parse '{}': {}", self.file.display(), e))?;
+        }
+        Ok(())
+    }
+
+    pub fn last(&self, image: &str) -> Option<&HistoryEntry> {
+        self.entries.get(image)
+    }
+
+    /// Record the outcome and save right away, so an interrupted run keeps what it did.
+    pub fn record(
+        &mut self,
+        image: &str,
+        action: &str,
+        ok: bool,
+        duration: Duration,
+    ) -> Result<(), String> {
+        self.entries.insert(
+            image.to_string(),
+            HistoryEntry {
+                action: action.to_string(),
+                ts: Local::now().timestamp(),
+                ok,
+                duration_secs: duration.as_secs(),
+            },
+        );
+        let json = serde_json::to_string_pretty(&self.entries)
+            .map_err(|e| format!("Failed to serialize history: {}", e))?;
+        fs::write(&self.file, json)
+            .map_err(|e| format!("Failed to write '{}': {}", self.file.display(), e))
+    }
+}
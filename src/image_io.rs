@@ -0,0 +1,156 @@
+use crate::args::{Args, BuildEngine};
+use crate::helpers::audit_helper_fns as audit;
+use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::engine_helper_fns;
+use crate::helpers::output_helper_fns as output;
+use crate::read_val;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TARBALL_EXTENSIONS: [&str; 4] = [".tar", ".tar.gz", ".tgz", ".tar.xz"];
+
+/// `podman load` (or `docker load`) every image tarball directly inside `dir`, for staging images on air-gapped hosts.
+pub fn import_images(dir: &Path, args: &Args) {
+    let mut tarballs: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                let name = p.file_name().unwrap().to_string_lossy();
+                TARBALL_EXTENSIONS.iter().any(|ext| name.ends_with(ext))
+            })
+            .collect(),
+        Err(e) => {
+            eprintln!("Error reading {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    tarballs.sort();
+
+    if args.verbose {
+        println!(
+            "Importing {} image tarball(s) from {}",
+            tarballs.len(),
+            dir.display()
+        );
+    }
+    for tarball in tarballs {
+        let path = tarball.display().to_string();
+        let loaded = cmd::exec_cmd(
+            engine_helper_fns::engine().store_binary(),
+            vec!["load", "-i", &path],
+        )
+        .success();
+        audit::record(args, "import", &path, audit::outcome(loaded));
+        if !loaded {
+            eprintln!("Error loading {}", path);
+        }
+    }
+}
+
+/// Ask where to save `image` (defaulting to a tarball named after it in the current dir) and `podman save` (or `docker save`) it there.
+pub fn export_image(image: &str, args: &Args) {
+    let default_path =
+        match output::default_output_path(args, "exports", &default_tarball_stem(image), "tar") {
+            Ok(path) => path,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        };
+    print!("Save {} to [{}]: ", image, default_path.display());
+    let input = read_val::read_line().unwrap();
+    let path = match input.trim() {
+        "" => default_path,
+        p => PathBuf::from(p),
+    };
+
+    let path_str = path.display().to_string();
+    let saved = cmd::exec_cmd(
+        engine_helper_fns::engine().store_binary(),
+        vec!["save", "-o", &path_str, image],
+    )
+    .success();
+    audit::record(args, "export", image, audit::outcome(saved));
+    if saved {
+        println!("Saved {} to {}", image, path_str);
+    } else {
+        eprintln!("Error saving {} to {}", image, path_str);
+    }
+}
+
+/// Add another name to `image` with `podman tag` (or `docker tag`), asking for it.
+pub fn tag_image(image: &str, args: &Args) {
+    print!("New name for {} (empty to cancel): ", image);
+    let input = read_val::read_line().unwrap();
+    let new_name = input.trim();
+    if new_name.is_empty() {
+        return;
+    }
+
+    let tagged = cmd::exec_cmd(
+        engine_helper_fns::engine().store_binary(),
+        vec!["tag", image, new_name],
+    )
+    .success();
+    audit::record(
+        args,
+        "tag",
+        image,
+        &format!("{} as {}", audit::outcome(tagged), new_name),
+    );
+    if tagged {
+        println!("Tagged {} as {}", image, new_name);
+    } else {
+        eprintln!("Error tagging {} as {}", image, new_name);
+    }
+}
+
+/// `podman push` (or `docker push`) the image, to its own name unless another destination is given.
+pub fn push_image(image: &str, args: &Args) {
+    print!("Push {} to [{}]: ", image, image);
+    let input = read_val::read_line().unwrap();
+    let destination = match input.trim() {
+        "" => image,
+        d => d,
+    };
+
+    let pushed = push(image, destination);
+    audit::record(
+        args,
+        "push",
+        image,
+        &format!("{} to {}", audit::outcome(pushed), destination),
+    );
+    if pushed {
+        println!("Pushed {} to {}", image, destination);
+    } else {
+        eprintln!("Error pushing {} to {}", image, destination);
+    }
+}
+
+fn push(image: &str, destination: &str) -> bool {
+    let binary = engine_helper_fns::engine().store_binary();
+    if engine_helper_fns::engine() != BuildEngine::Docker {
+        return cmd::exec_cmd(binary, vec!["push", image, destination]).success();
+    }
+    // docker push only takes the name to push, so another destination is tagged first
+    (destination == image || cmd::exec_cmd(binary, vec!["tag", image, destination]).success())
+        && cmd::exec_cmd(binary, vec!["push", destination]).success()
+}
+
+/// `docker.io/library/nginx:latest` -> `docker.io_library_nginx_latest`
+fn default_tarball_stem(image: &str) -> String {
+    image
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,39 @@
+use crate::helpers::git_helper_fns;
+
+use chrono::Local;
+use std::path::Path;
+
+/// The image name for `buildfile` per --image-name-template. `{dir}` is the build file's dir name,
+/// `{suffix}` the `<name>` of a `Dockerfile.<name>` (empty for a plain one), `{branch}` the git
+/// branch checked out there (`latest` outside a repo or on a detached HEAD) and `{date}` today as
+/// YYYYMMDD. Names are lowercased and slashes in the branch become dashes, as image refs need.
+pub fn from_template(template: &str, buildfile: &Path) -> String {
+    let dir = buildfile.parent().unwrap_or(Path::new("."));
+    let dir_name = dir
+        .canonicalize()
+        .ok()
+        .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let suffix = buildfile
+        .file_name()
+        .and_then(|n| {
+            n.to_string_lossy()
+                .split_once('.')
+                .map(|(_, s)| s.to_string())
+        })
+        .unwrap_or_default();
+    let mut name = template
+        .replace("{dir}", &dir_name.to_lowercase())
+        .replace("{suffix}", &suffix.to_lowercase())
+        .replace("{date}", &Local::now().format("%Y%m%d").to_string());
+    if name.contains("{branch}") {
+        name = name.replace(
+            "{branch}",
+            &git_helper_fns::branch(dir)
+                .unwrap_or_else(|| "latest".to_string())
+                .replace('/', "-")
+                .to_lowercase(),
+        );
+    }
+    name
+}
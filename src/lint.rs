@@ -0,0 +1,458 @@
+use crate::args::Args;
+use crate::compose;
+
+use regex::Regex;
+use serde::Serialize;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const TOP_LEVEL_KEYS: [&str; 8] = [
+    "version", "name", "include", "services", "networks", "volumes", "configs", "secrets",
+];
+
+/// Service keys in the compose-spec.
+const SERVICE_KEYS: [&str; 87] = [
+    "annotations",
+    "attach",
+    "blkio_config",
+    "build",
+    "cap_add",
+    "cap_drop",
+    "cgroup",
+    "cgroup_parent",
+    "command",
+    "configs",
+    "container_name",
+    "cpu_count",
+    "cpu_percent",
+    "cpu_period",
+    "cpu_quota",
+    "cpu_rt_period",
+    "cpu_rt_runtime",
+    "cpu_shares",
+    "cpus",
+    "cpuset",
+    "credential_spec",
+    "depends_on",
+    "deploy",
+    "develop",
+    "device_cgroup_rules",
+    "devices",
+    "dns",
+    "dns_opt",
+    "dns_search",
+    "domainname",
+    "driver_opts",
+    "entrypoint",
+    "env_file",
+    "environment",
+    "expose",
+    "extends",
+    "external_links",
+    "extra_hosts",
+    "gpus",
+    "group_add",
+    "healthcheck",
+    "hostname",
+    "image",
+    "init",
+    "ipc",
+    "isolation",
+    "labels",
+    "label_file",
+    "links",
+    "logging",
+    "mac_address",
+    "mem_limit",
+    "mem_reservation",
+    "mem_swappiness",
+    "memswap_limit",
+    "network_mode",
+    "networks",
+    "oom_kill_disable",
+    "oom_score_adj",
+    "pid",
+    "pids_limit",
+    "platform",
+    "ports",
+    "post_start",
+    "pre_stop",
+    "privileged",
+    "profiles",
+    "pull_policy",
+    "read_only",
+    "restart",
+    "runtime",
+    "scale",
+    "secrets",
+    "security_opt",
+    "shm_size",
+    "stdin_open",
+    "stop_grace_period",
+    "stop_signal",
+    "storage_opt",
+    "sysctls",
+    "tmpfs",
+    "tty",
+    "ulimits",
+    "user",
+    "userns_mode",
+    "volumes",
+    "working_dir",
+];
+
+/// Image reference rules and their default severity, overridable with --lint-rule.
+pub const IMAGE_RULES: [(&str, Severity); 3] = [
+    // no tag or digest, so podman uses :latest
+    ("implicit-tag", Severity::Warning),
+    // :latest and friends in a dir matching --production-path-patterns
+    ("floating-tag", Severity::Error),
+    // registry host that's a near miss of a well known one
+    ("registry-typo", Severity::Warning),
+];
+
+const FLOATING_TAGS: [&str; 8] = [
+    "latest", "stable", "edge", "nightly", "main", "master", "develop", "dev",
+];
+
+const KNOWN_REGISTRIES: [&str; 10] = [
+    "docker.io",
+    "quay.io",
+    "ghcr.io",
+    "gcr.io",
+    "lscr.io",
+    "mcr.microsoft.com",
+    "public.ecr.aws",
+    "registry.gitlab.com",
+    "registry.fedoraproject.org",
+    "registry.access.redhat.com",
+];
+
+/// Rule severities and which paths count as production, from args.
+pub struct LintConfig {
+    severities: HashMap<String, Option<Severity>>,
+    production_patterns: Vec<Regex>,
+}
+
+impl LintConfig {
+    pub fn from_args(args: &Args) -> Result<LintConfig, String> {
+        let mut severities: HashMap<String, Option<Severity>> = IMAGE_RULES
+            .iter()
+            .map(|(rule, severity)| (rule.to_string(), Some(*severity)))
+            .collect();
+        for setting in &args.lint_rule {
+            let (rule, level) = setting
+                .split_once('=')
+                .ok_or(format!("--lint-rule '{}' should be RULE=LEVEL", setting))?;
+            if !severities.contains_key(rule) {
+                return Err(format!("Unknown lint rule '{}'", rule));
+            }
+            let severity = match level {
+                "off" => None,
+                "info" => Some(Severity::Info),
+                "warning" => Some(Severity::Warning),
+                "error" => Some(Severity::Error),
+                _ => {
+                    return Err(format!(
+                        "Unknown level '{}' for --lint-rule {}, use off, info, warning or error",
+                        level, rule
+                    ))
+                }
+            };
+            severities.insert(rule.to_string(), severity);
+        }
+        let production_patterns = args
+            .production_path_patterns
+            .iter()
+            .map(|p| Regex::new(p).map_err(|e| format!("Invalid pattern '{}': {}", p, e)))
+            .collect::<Result<Vec<Regex>, String>>()?;
+        Ok(LintConfig {
+            severities,
+            production_patterns,
+        })
+    }
+
+    fn severity(&self, rule: &str) -> Option<Severity> {
+        self.severities.get(rule).copied().flatten()
+    }
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Finding {
+    pub file: PathBuf,
+    /// 1-based, 0 when the finding isn't about a particular line
+    pub line: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.file.display(), self.line)?;
+        } else {
+            write!(f, "{}: ", self.file.display())?;
+        }
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Print the findings, as `file:line: severity: message` lines or with --json as a JSON array.
+/// Returns false if any is an error.
+pub fn report(findings: &[Finding], json: bool) -> bool {
+    if json {
+        println!("{}", serde_json::to_string_pretty(findings).unwrap());
+    } else {
+        for finding in findings {
+            println!("{}", finding);
+        }
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    if !json {
+        println!("{} finding(s), {} error(s).", findings.len(), errors);
+    }
+    errors == 0
+}
+
+/// Check a compose file against the compose-spec: parse errors, the obsolete `version` key,
+/// unknown top-level and service keys (with a suggestion for typos), and services with neither `image` nor `build`.
+/// Then check each image reference against the IMAGE_RULES.
+pub fn lint_compose_file(file: &Path, config: &LintConfig) -> Vec<Finding> {
+    let mut findings = vec![];
+    let mut add = |line: usize, severity: Severity, message: String| {
+        findings.push(Finding {
+            file: file.to_path_buf(),
+            line,
+            severity,
+            message,
+        })
+    };
+
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(e) => {
+            add(0, Severity::Error, format!("can't read file: {}", e));
+            return findings;
+        }
+    };
+    let yaml: Value = match serde_yaml::from_str(&content) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            let line = e.location().map(|l| l.line()).unwrap_or(0);
+            add(line, Severity::Error, format!("invalid yaml: {}", e));
+            return findings;
+        }
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let top_level = match yaml.as_mapping() {
+        Some(map) => map,
+        None => {
+            add(1, Severity::Error, "top level is not a mapping".to_string());
+            return findings;
+        }
+    };
+
+    for key in top_level.keys().filter_map(|k| k.as_str()) {
+        let line = key_line(&lines, 0, false, key);
+        if key == "version" {
+            add(
+                line,
+                Severity::Info,
+                "`version` is obsolete in the compose-spec and ignored".to_string(),
+            );
+        } else if !key.starts_with("x-") && !TOP_LEVEL_KEYS.contains(&key) {
+            add(
+                line,
+                Severity::Error,
+                unknown_key_message(key, &TOP_LEVEL_KEYS),
+            );
+        }
+    }
+
+    let services = match yaml.get("services").and_then(|s| s.as_mapping()) {
+        Some(services) => services,
+        None => {
+            add(0, Severity::Error, "no `services` defined".to_string());
+            return findings;
+        }
+    };
+    let services_line = key_line(&lines, 0, false, "services");
+    let production = config
+        .production_patterns
+        .iter()
+        .any(|p| p.is_match(&file.to_string_lossy()));
+    for (name, service_config) in services {
+        let name = match name.as_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let service_line = key_line(&lines, services_line, true, name);
+        if let Some((image, _)) = compose::resolved_image(file, service_config) {
+            let image_line = key_line(&lines, service_line, true, "image");
+            for (rule, message) in image_problems(&image, production) {
+                if let Some(severity) = config.severity(rule) {
+                    add(
+                        image_line,
+                        severity,
+                        format!("service `{}`: {} [{}]", name, message, rule),
+                    );
+                }
+            }
+        }
+        let config = match service_config.as_mapping() {
+            Some(config) => config,
+            None => {
+                add(
+                    service_line,
+                    Severity::Error,
+                    format!("service `{}` is not a mapping", name),
+                );
+                continue;
+            }
+        };
+        for key in config.keys().filter_map(|k| k.as_str()) {
+            if !key.starts_with("x-") && !SERVICE_KEYS.contains(&key) {
+                add(
+                    key_line(&lines, service_line, true, key),
+                    Severity::Error,
+                    format!(
+                        "service `{}`: {}",
+                        name,
+                        unknown_key_message(key, &SERVICE_KEYS)
+                    ),
+                );
+            }
+        }
+        if let Err(e) = compose::service_meta(service_config) {
+            add(
+                key_line(&lines, service_line, true, compose::META_KEY),
+                Severity::Error,
+                format!("service `{}`: {}", name, e),
+            );
+        }
+        if !config.contains_key("image") && !config.contains_key("build") {
+            add(
+                service_line,
+                Severity::Warning,
+                format!("service `{}` has neither `image` nor `build`", name),
+            );
+        }
+    }
+    findings
+}
+
+/// Rule name and message for each IMAGE_RULES problem with `image`.
+fn image_problems(image: &str, production: bool) -> Vec<(&'static str, String)> {
+    let mut problems = vec![];
+    let (name, digest) = match image.split_once('@') {
+        Some((name, digest)) => (name, Some(digest)),
+        None => (image, None),
+    };
+    let last_component = name.rsplit('/').next().unwrap_or(name);
+    let tag = last_component.split_once(':').map(|(_, tag)| tag);
+
+    if tag.is_none() && digest.is_none() {
+        problems.push((
+            "implicit-tag",
+            format!("`{}` has no tag, so it's :latest", image),
+        ));
+    }
+    if production && digest.is_none() {
+        let tag = tag.unwrap_or("latest");
+        if FLOATING_TAGS.contains(&tag) {
+            problems.push((
+                "floating-tag",
+                format!(
+                    "`{}` uses floating tag :{} in a production path, pin a version or digest",
+                    image, tag
+                ),
+            ));
+        }
+    }
+    if let Some((registry, _)) = name.split_once('/') {
+        let looks_like_host =
+            registry.contains('.') || registry.contains(':') || registry == "localhost";
+        if looks_like_host && !KNOWN_REGISTRIES.contains(&registry) {
+            if let Some(known) = KNOWN_REGISTRIES.iter().find(|known| {
+                edit_distance(registry, known) <= if known.len() < 10 { 1 } else { 2 }
+            }) {
+                problems.push((
+                    "registry-typo",
+                    format!("registry `{}` looks like a typo of `{}`", registry, known),
+                ));
+            }
+        }
+    }
+    problems
+}
+
+/// Line of the first `key:` after `after_line`, at the top level or indented,
+/// since serde_yaml doesn't keep positions. Falls back to `after_line`.
+fn key_line(lines: &[&str], after_line: usize, indented: bool, key: &str) -> usize {
+    let prefixes = [
+        format!("{}:", key),
+        format!("\"{}\":", key),
+        format!("'{}':", key),
+    ];
+    lines
+        .iter()
+        .enumerate()
+        .skip(after_line)
+        .find(|(_, line)| {
+            let trimmed = line.trim_start();
+            let indent = line.len() - trimmed.len();
+            indented == (indent > 0) && prefixes.iter().any(|p| trimmed.starts_with(p))
+        })
+        .map(|(i, _)| i + 1)
+        .unwrap_or(after_line)
+}
+
+fn unknown_key_message(key: &str, known: &[&str]) -> String {
+    match known
+        .iter()
+        .map(|k| (edit_distance(key, k), k))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+    {
+        Some((_, suggestion)) => format!("unknown key `{}`, did you mean `{}`?", key, suggestion),
+        None => format!("unknown key `{}`", key),
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,148 @@
+use crate::args::{Args, ListFormat, Mode};
+use crate::compose;
+use crate::helpers::dockerfile_helper_fns;
+use crate::helpers::podman_helper_fns;
+use crate::rebuild;
+
+use chrono::{DateTime, Datelike, Local, SecondsFormat};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Value;
+use std::path::{Path, PathBuf};
+
+/// One service with an image, as `list` prints it. Drift mode reads these back from json.
+#[derive(Serialize, Deserialize)]
+pub struct ListRow {
+    pub image: String,
+    pub service: String,
+    pub container: Option<String>,
+    pub dir: PathBuf,
+    pub build_file: Option<PathBuf>,
+    /// Only looked up for `wide` and `json`, since it takes a podman call per image
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "rfc3339",
+        deserialize_with = "from_rfc3339"
+    )]
+    pub created: Option<DateTime<Local>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "rfc3339",
+        deserialize_with = "from_rfc3339"
+    )]
+    pub pulled: Option<DateTime<Local>>,
+}
+
+pub fn rows(compose_file: &Path, yaml: &Value, args: &Args) -> Vec<ListRow> {
+    let dir = compose_file
+        .parent()
+        .unwrap_or(Path::new("."))
+        .to_path_buf();
+    let build_file = dockerfile_helper_fns::find_buildfile(&dir);
+    let with_dates = args.format != ListFormat::Table || matches!(args.mode, Mode::Drift);
+    compose::services(yaml)
+        .into_iter()
+        .filter_map(|(service, config)| {
+            let (image, _) = compose::resolved_image(compose_file, config)?;
+            let (created, pulled) = if with_dates {
+                (
+                    // podman reports images it doesn't have as created in 1900
+                    podman_helper_fns::get_podman_image_upstream_create_time(&image)
+                        .ok()
+                        .filter(|dt| dt.year() > 1900),
+                    podman_helper_fns::get_podman_ondisk_modify_time(&image).ok(),
+                )
+            } else {
+                (None, None)
+            };
+            Some(ListRow {
+                container: config
+                    .get("container_name")
+                    .and_then(|c| c.as_str())
+                    .map(|c| c.to_string()),
+                image,
+                service,
+                dir: dir.clone(),
+                build_file: build_file.clone(),
+                created,
+                pulled,
+            })
+        })
+        .collect()
+}
+
+pub fn print(rows: &[ListRow], args: &Args) {
+    if args.format == ListFormat::Json {
+        println!("{}", serde_json::to_string_pretty(rows).unwrap());
+        return;
+    }
+    let wide = args.format == ListFormat::Wide;
+    let time_format = args.time_format.unwrap_or_default();
+    let date = |dt: &Option<DateTime<Local>>| match dt {
+        Some(dt) => rebuild::format_time(*dt, time_format),
+        None => "-".to_string(),
+    };
+
+    let mut table: Vec<Vec<String>> = vec![];
+    let mut header = vec!["IMAGE", "SERVICE", "CONTAINER", "DIR"];
+    if wide {
+        header.extend(["BUILD FILE", "CREATED", "PULLED"]);
+    }
+    table.push(header.iter().map(|h| h.to_string()).collect());
+    for row in rows {
+        let mut cells = vec![
+            row.image.clone(),
+            row.service.clone(),
+            row.container.clone().unwrap_or_else(|| "-".to_string()),
+            row.dir.display().to_string(),
+        ];
+        if wide {
+            cells.push(
+                row.build_file
+                    .as_ref()
+                    .and_then(|f| f.file_name())
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            );
+            cells.push(date(&row.created));
+            cells.push(date(&row.pulled));
+        }
+        table.push(cells);
+    }
+
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|i| {
+            table
+                .iter()
+                .map(|r| r[i].chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    for cells in &table {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+}
+
+/// RFC 3339 in json, whatever --time-format says.
+fn rfc3339<S: Serializer>(dt: &Option<DateTime<Local>>, serializer: S) -> Result<S::Ok, S::Error> {
+    dt.map(|dt| dt.to_rfc3339_opts(SecondsFormat::Secs, false))
+        .serialize(serializer)
+}
+
+fn from_rfc3339<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Local>>, D::Error> {
+    match Option::<String>::deserialize(deserializer)? {
+        Some(s) => DateTime::parse_from_rfc3339(&s)
+            .map(|dt| Some(dt.with_timezone(&Local)))
+            .map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
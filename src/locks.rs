@@ -0,0 +1,69 @@
+use crate::state::StateDir;
+
+use chrono::Local;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+
+/// Held while an image is pulled or built, so two runs don't build the same tag at once. The lock
+/// is released when this is dropped, or by the OS if the run dies.
+pub struct ImageLock {
+    _file: Option<File>,
+    /// unix seconds when we started waiting on another run, if we had to
+    pub waited_since: Option<i64>,
+}
+
+impl ImageLock {
+    /// Lock `image` in the state dir's `locks/`, waiting if another run holds it.
+    pub fn acquire(state: &StateDir, image: &str) -> Result<ImageLock, String> {
+        let file = lock_file(state, image);
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&file)
+            .map_err(|e| format!("Failed to open '{}': {}", file.display(), e))?;
+        let waited_since = match lock.try_lock() {
+            Ok(()) => None,
+            Err(TryLockError::WouldBlock) => {
+                println!(
+                    "Waiting on lock for {}, another run is pulling or building it...",
+                    image
+                );
+                let since = Local::now().timestamp();
+                lock.lock()
+                    .map_err(|e| format!("Failed to lock '{}': {}", file.display(), e))?;
+                Some(since)
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(format!("Failed to lock '{}': {}", file.display(), e))
+            }
+        };
+        Ok(ImageLock {
+            _file: Some(lock),
+            waited_since,
+        })
+    }
+
+    /// Stand-in when the state dir can't be used; guards nothing.
+    pub fn unlocked() -> ImageLock {
+        ImageLock {
+            _file: None,
+            waited_since: None,
+        }
+    }
+}
+
+/// One file per image, with characters that can't go in a file name (`/`, `:`) replaced.
+fn lock_file(state: &StateDir, image: &str) -> PathBuf {
+    let name: String = image
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    state.path().join("locks").join(format!("{}.lock", name))
+}
@@ -1,52 +1,196 @@
+mod answers;
 mod args;
+mod buildcache;
+mod buildfiles;
+mod compose;
+mod depgraph;
+mod diagnostics;
+mod dockerfilelint;
+mod doctor;
+mod drift;
+mod error;
+mod exclusions;
+mod exit_code;
 mod rebuild;
 mod helpers {
+    pub mod audit_helper_fns;
     pub mod cmd_helper_fns;
+    pub mod color_helper_fns;
+    pub mod diff_helper_fns;
+    pub mod dockerfile_helper_fns;
+    pub mod encryption_helper_fns;
+    pub mod engine_helper_fns;
+    pub mod git_helper_fns;
+    pub mod host_helper_fns;
+    pub mod ignore_helper_fns;
+    pub mod image_name_helper_fns;
+    pub mod log_sink_helper_fns;
+    pub mod output_helper_fns;
     pub mod podman_helper_fns;
+    pub mod redact_helper_fns;
+    pub mod signing_helper_fns;
+    pub mod signal_helper_fns;
 }
+mod history;
+mod image_io;
+mod imagename;
+mod lint;
+mod list;
+mod locks;
+mod metrics;
+mod netcheck;
+mod project;
+mod quadlet;
 mod read_val;
+mod repocheck;
 mod restartsvcs;
+mod retag;
+mod scan;
 mod secrets;
+mod session;
+mod state;
+mod usage;
 
 use args::Args;
+use depgraph::DependencyGraph;
+use error::AppError;
 use rebuild::RebuildManager;
 use regex::Regex;
 // use futures::executor;
-use std::{ io, mem };
+use std::{ cell::Cell, mem, rc::Rc, time::{ Duration, Instant } };
 use walkdir::WalkDir;
 
-fn main() -> io::Result<()> {
+fn main() {
     // Parse command-line arguments
     let args = args::args_checks();
-    if let Err(e) = args.validate() {
-        eprintln!("Error: {}", e);
-        std::process::exit(1);
+    if let Err(e) = run(args) {
+        e.report();
+        std::process::exit(e.exit_code());
     }
+}
 
-    match args.mode {
+fn run(mut args: Args) -> Result<(), AppError> {
+    if let Some(project_file) = args.project.clone() {
+        match project::load(&project_file, args.profile.as_deref()) {
+            Ok(project) => {
+                if args.verbose {
+                    match &args.profile {
+                        Some(profile) => {
+                            println!("Project: {} ({})", project.display_name(), profile)
+                        }
+                        None => println!("Project: {}", project.display_name()),
+                    }
+                }
+                project.apply_to(&mut args);
+                args.loaded_project = Some(project);
+            }
+            Err(e) => return Err(AppError::Usage(e)),
+        }
+    }
+    args.validate().map_err(AppError::Usage)?;
+
+    if args.redact_secrets {
+        let input_json = args.secret_mode_input_json.as_ref().unwrap();
+        let values =
+            helpers::redact_helper_fns::load_inventory(input_json).map_err(AppError::Usage)?;
+        helpers::redact_helper_fns::init(values);
+    }
+    if let Some(spec) = &args.log_sink {
+        helpers::log_sink_helper_fns::init(spec).map_err(AppError::Usage)?;
+    }
+    helpers::signal_helper_fns::install(args.audit_log.clone());
+    helpers::engine_helper_fns::set_scope(args.podman_scope);
+    let engine = helpers::engine_helper_fns::init(args.build_engine);
+    if args.verbose && args.build_engine.is_none() {
+        println!("Using {} to pull and build.", engine.binary());
+    }
+    if !args.platforms.is_empty() && engine == args::BuildEngine::Docker {
+        return Err(AppError::Usage(
+            "--platforms needs podman or buildah, and docker is the only one found.".to_string(),
+        ));
+    }
+    if let Some(secs) = args.bell_after {
+        read_val::set_bell_after(secs);
+    }
+
+    let result = match args.mode {
         args::Mode::SecretRefresh => {
-            if let Err(e) = secrets::update_mode(&args) {
-                eprintln!("Error refreshing secrets: {}", e);
+            secrets::update_mode(&args).map_err(|e| AppError::Secrets("refreshing secrets", e))
+        }
+        args::Mode::SecretRetrieve => match secrets::validate(&args) {
+            Ok(()) if secrets::mismatches() > 0 => {
+                Err(AppError::SecretsMismatch(secrets::mismatches()))
             }
+            Ok(()) => Ok(()),
+            Err(e) => Err(AppError::Secrets("retrieving secrets", e)),
+        },
+        args::Mode::SecretCheckAuth => match secrets::check_auth(&args) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(AppError::SecretsAuth),
+            Err(e) => Err(AppError::Secrets("checking secrets credentials", e)),
+        },
+        args::Mode::SecretGc => {
+            secrets::gc(&args).map_err(|e| AppError::Secrets("collecting orphaned secrets", e))
         }
-        args::Mode::SecretRetrieve => {
-            if let Err(e) = secrets::validate(&args) {
-                eprintln!("Error retrieving secrets: {}", e);
+        args::Mode::StateClean => match state::StateDir::open(&args).and_then(|s| {
+            s.clean()?;
+            Ok(s)
+        }) {
+            Ok(s) => {
+                let target = s.path().display().to_string();
+                helpers::audit_helper_fns::record(&args, "state-clean", &target, "ok");
+                println!("Cleaned state dir {}", s.path().display());
+                Ok(())
+            }
+            Err(e) => {
+                helpers::audit_helper_fns::record(&args, "state-clean", "", &e);
+                Err(AppError::Failed("cleaning state dir", e))
             }
+        },
+        args::Mode::Diagnostics => match diagnostics::create_bundle(&args) {
+            Ok(bundle) => {
+                println!("Wrote {}", bundle.display());
+                Ok(())
+            }
+            Err(e) => Err(AppError::Failed("creating diagnostics bundle", e)),
+        },
+        args::Mode::Doctor => found_problems(!doctor::run(&args)),
+        args::Mode::RepoCheck => {
+            found_problems(!lint::report(&repocheck::check_repository(&args), args.json))
+        }
+        args::Mode::Retag => found_problems(!retag::run(&args)),
+        args::Mode::UsageReport => {
+            usage::print(&usage::report(&repocheck::image_users(&args)), args.json);
+            Ok(())
         }
         _ => {
-            walk_dirs(&args);
+            if let Some(dir) = &args.import_images {
+                image_io::import_images(dir, &args);
+            }
+            match args.daemon {
+                Some(interval) => run_daemon(&args, interval),
+                None => walk_dirs(&args),
+            }
         }
-    }
+    };
 
     if args.verbose {
         println!("Done.");
     }
+    result
+}
 
-    Ok(())
+/// Problems a mode has printed already, as an error for the exit code.
+fn found_problems(found: bool) -> Result<(), AppError> {
+    if found {
+        Err(AppError::Findings)
+    } else {
+        Ok(())
+    }
 }
 
-fn walk_dirs(args: &Args) {
+/// Err if a scan root couldn't be read, any pull or build failed, or lint or drift found something.
+fn walk_dirs(args: &Args) -> Result<(), AppError> {
     let mut exclude_patterns = Vec::new();
     let mut include_patterns = Vec::new();
 
@@ -67,16 +211,95 @@ fn walk_dirs(args: &Args) {
         }
     }
 
+    let roots = args.scan_roots();
     if args.verbose {
-        println!("Rebuild images in path: {}", args.path.display());
+        for root in &roots {
+            println!("Rebuild images in path: {}", root.display());
+        }
+    }
+
+    // report registries that are down now, rather than as each pull from them fails; a daemon
+    // checks once at start instead of every interval
+    if matches!(args.mode, args::Mode::Rebuild) && !args.skip_preflight && args.daemon.is_none() {
+        netcheck::preflight(args);
     }
 
-    let mut manager: Option<RebuildManager> = Some(rebuild::RebuildManager::new());
+    // dirs excluded with the `x` prompt choice, in earlier runs or in this one
+    let exclusions = match
+        state::StateDir::open(args).and_then(|state| exclusions::Exclusions::load(&state))
+    {
+        Ok(exclusions) => Some(Rc::new(exclusions)),
+        Err(e) => {
+            helpers::color_helper_fns::warn(format!("not applying excluded dirs: {}", e));
+            None
+        }
+    };
+    let mut manager: Option<RebuildManager> = Some(
+        rebuild::RebuildManager::new(args, exclusions.clone()).map_err(AppError::Usage)?
+    );
+    let mut graphs: Vec<DependencyGraph> = vec![];
+    let mut findings: Vec<lint::Finding> = vec![];
+    let mut list_rows: Vec<list::ListRow> = vec![];
+    let lint_config = lint::LintConfig::from_args(args).map_err(AppError::Usage)?;
 
-    for entry in WalkDir::new(&args.path)
-        .into_iter()
-        .filter_map(|e| e.ok()) {
-        if entry.file_type().is_file() && entry.file_name() == "docker-compose.yml" {
+    let ignore_rules = helpers::ignore_helper_fns::IgnoreRules::new();
+    let unreadable_roots = Cell::new(0);
+    let mut walker = roots.iter().flat_map(|root| {
+        WalkDir::new(root)
+            .into_iter()
+            .filter_entry(|e| {
+                e.depth() == 0 ||
+                    args.no_ignore ||
+                    !(
+                        ignore_rules.is_ignored(root, e.path(), e.file_type().is_dir()) ||
+                        exclusions.as_ref().is_some_and(|x| x.covers(e.path()))
+                    )
+            })
+            .filter_map(|e| match e {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    // deeper errors are unreadable subdirs, which the scan has always skipped quietly
+                    if e.depth() == 0 {
+                        eprintln!("Error reading {}: {}", root.display(), e);
+                        unreadable_roots.set(unreadable_roots.get() + 1);
+                    }
+                    None
+                }
+            })
+    });
+    // only time spent walking counts against --scan-timeout, not time spent at prompts
+    let mut scan_time = Duration::ZERO;
+    let mut dirs_visited = 0;
+    let mut scanned_all = false;
+    loop {
+        let step = Instant::now();
+        let Some(entry) = walker.next() else {
+            scanned_all = true;
+            break;
+        };
+        scan_time += step.elapsed();
+        if entry.file_type().is_dir() {
+            dirs_visited += 1;
+        }
+        if let Some(timeout) = args.scan_timeout.filter(|timeout| scan_time > *timeout) {
+            eprintln!(
+                "Scan truncated after {}s (--scan-timeout), {} dir(s) visited; the rest were not scanned.",
+                timeout.as_secs(),
+                dirs_visited
+            );
+            break;
+        }
+        let is_compose_file = compose::is_primary_compose_file(entry.path());
+        // with --image-name-template, rebuild mode also offers dirs with only a build file in them
+        let is_unnamed_buildfile =
+            !is_compose_file &&
+            args.image_name_template.is_some() &&
+            matches!(args.mode, args::Mode::Rebuild) &&
+            entry.path().parent().is_some_and(|dir| {
+                let buildfile = helpers::dockerfile_helper_fns::find_buildfile(dir);
+                buildfile.as_deref() == Some(entry.path()) && repocheck::nothing_names_image(dir)
+            });
+        if entry.file_type().is_file() && (is_compose_file || is_unnamed_buildfile) {
             if
                 exclude_patterns.len() > 0 &&
                 exclude_patterns
@@ -97,17 +320,121 @@ fn walk_dirs(args: &Args) {
                 args::Mode::Rebuild => {
                     // let mut manager = rebuild::RebuildManager::new();
                     if let Some(ref mut manager) = manager {
-                        manager.rebuild(&entry, &args);
+                        if is_compose_file {
+                            manager.rebuild(&entry, &args);
+                        } else {
+                            manager.rebuild_buildfile(&entry, args);
+                        }
+                        if manager.should_stop(args) {
+                            eprintln!("Stopping after {} failure(s).", manager.failed());
+                            break;
+                        }
                     }
                 }
                 args::Mode::RestartSvcs => {
                     drop_mgr(&mut manager);
                     restartsvcs::restart_services(&args);
                 }
+                args::Mode::ComposeToQuadlet => {
+                    drop_mgr(&mut manager);
+                    quadlet::convert(entry.path(), args);
+                }
+                args::Mode::DependencyGraph => {
+                    drop_mgr(&mut manager);
+                    match compose::read_compose(entry.path()) {
+                        Ok(yaml) => {
+                            let graph = DependencyGraph::from_yaml(entry.path(), &yaml);
+                            print!("{}", graph.render_tree());
+                            graphs.push(graph);
+                        }
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                args::Mode::List | args::Mode::Drift => {
+                    drop_mgr(&mut manager);
+                    match compose::read_compose(entry.path()) {
+                        Ok(yaml) => list_rows.extend(list::rows(entry.path(), &yaml, args)),
+                        Err(e) => eprintln!("Error: {}", e),
+                    }
+                }
+                args::Mode::Lint => {
+                    drop_mgr(&mut manager);
+                    findings.extend(lint::lint_compose_file(entry.path(), &lint_config));
+                }
                 _ => {}
             }
         }
     }
+
+    if let Some(dot_file) = &args.dot_output {
+        if let Err(e) = std::fs::write(dot_file, depgraph::to_dot(&graphs)) {
+            eprintln!("Error writing {}: {}", dot_file.display(), e);
+        } else if args.verbose {
+            println!("Wrote dependency graph to {}", dot_file.display());
+        }
+    }
+
+    if let args::Mode::List = args.mode {
+        list::print(&list_rows, args);
+        drop_mgr(&mut manager);
+    }
+
+    if let args::Mode::Drift = args.mode {
+        drop_mgr(&mut manager);
+        let baseline =
+            drift::load_baseline(args.baseline.as_ref().unwrap()).map_err(AppError::Usage)?;
+        found_problems(!drift::report(&drift::compare(&baseline, &list_rows), args.json))?;
+    }
+
+    if let args::Mode::Lint = args.mode {
+        found_problems(!lint::report(&findings, args.json))?;
+        drop_mgr(&mut manager);
+    }
+
+    if args.verbose && ignore_rules.ignored_count() > 0 {
+        println!("Skipped {} ignored path(s).", ignore_rules.ignored_count());
+    }
+
+    if let Some(mut manager) = manager {
+        if scanned_all {
+            manager.finish_session();
+        }
+        if manager.bytes_reclaimed() > 0 {
+            println!(
+                "Reclaimed {} from pruned images.",
+                helpers::cmd_helper_fns::format_bytes(manager.bytes_reclaimed())
+            );
+        }
+        println!("{}", manager.summary());
+        if manager.failed() > 0 {
+            return Err(AppError::Build(manager.failed()));
+        }
+    }
+    if unreadable_roots.get() > 0 {
+        return Err(AppError::Discovery(unreadable_roots.get()));
+    }
+    Ok(())
+}
+
+/// Scan, wait `interval`, scan again, until a signal stops us.
+fn run_daemon(args: &Args, interval: Duration) -> Result<(), AppError> {
+    if let Some(addr) = args.metrics_addr {
+        metrics::serve(addr).map_err(AppError::Usage)?;
+        println!("Serving metrics at http://{}/metrics", addr);
+    }
+    if !args.skip_preflight {
+        netcheck::preflight(args);
+    }
+    loop {
+        let result = walk_dirs(args);
+        metrics::record_scan();
+        if let Err(e) = result {
+            e.report();
+            eprintln!("Scan finished with exit code {}.", e.exit_code());
+        }
+        println!("Next scan in {}s.", interval.as_secs());
+        std::thread::sleep(interval);
+    }
 }
 
 fn drop_mgr(manager: &mut Option<RebuildManager>) {
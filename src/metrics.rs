@@ -0,0 +1,134 @@
+use chrono::Local;
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+/// How long a scraper gets to send its request or take the response, as one at a time is served.
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+
+struct Metrics {
+    scans: u64,
+    last_scan: Option<i64>,
+    /// (action, outcome) -> how many
+    runs: BTreeMap<(String, &'static str), u64>,
+    /// (image, action) -> unix time of its last pull or build
+    last_run: BTreeMap<(String, String), i64>,
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    scans: 0,
+    last_scan: None,
+    runs: BTreeMap::new(),
+    last_run: BTreeMap::new(),
+});
+
+pub fn record_scan() {
+    let mut metrics = METRICS.lock().unwrap();
+    metrics.scans += 1;
+    metrics.last_scan = Some(Local::now().timestamp());
+}
+
+pub fn record_run(image: &str, action: &str, ok: bool) {
+    let mut metrics = METRICS.lock().unwrap();
+    let outcome = if ok { "ok" } else { "failed" };
+    *metrics
+        .runs
+        .entry((action.to_string(), outcome))
+        .or_insert(0) += 1;
+    metrics.last_run.insert(
+        (image.to_string(), action.to_string()),
+        Local::now().timestamp(),
+    );
+}
+
+/// Answer `GET /metrics` on `addr` from a background thread, in the Prometheus text format.
+pub fn serve(addr: SocketAddr) -> Result<(), String> {
+    let listener =
+        TcpListener::bind(addr).map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            // a scraper that hangs up early isn't worth reporting
+            let _ = respond(stream);
+        }
+    });
+    Ok(())
+}
+
+fn respond(stream: TcpStream) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    stream.set_write_timeout(Some(IO_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+    let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+        ["GET", "/metrics"] => ("200 OK", render()),
+        _ => ("404 Not Found", "Not found, try /metrics\n".to_string()),
+    };
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+fn render() -> String {
+    let metrics = METRICS.lock().unwrap();
+    let mut out = String::new();
+    out.push_str("# HELP podman_compose_mgr_scans_total Scans of the compose dirs finished.\n");
+    out.push_str("# TYPE podman_compose_mgr_scans_total counter\n");
+    out.push_str(&format!(
+        "podman_compose_mgr_scans_total {}\n",
+        metrics.scans
+    ));
+    if let Some(last_scan) = metrics.last_scan {
+        out.push_str(
+            "# HELP podman_compose_mgr_last_scan_timestamp_seconds When the last scan finished.\n",
+        );
+        out.push_str("# TYPE podman_compose_mgr_last_scan_timestamp_seconds gauge\n");
+        out.push_str(&format!(
+            "podman_compose_mgr_last_scan_timestamp_seconds {}\n",
+            last_scan
+        ));
+    }
+    out.push_str("# HELP podman_compose_mgr_runs_total Pulls and builds, by outcome.\n");
+    out.push_str("# TYPE podman_compose_mgr_runs_total counter\n");
+    for ((action, outcome), count) in &metrics.runs {
+        out.push_str(&format!(
+            "podman_compose_mgr_runs_total{{action=\"{}\",outcome=\"{}\"}} {}\n",
+            label(action),
+            outcome,
+            count
+        ));
+    }
+    out.push_str(
+        "# HELP podman_compose_mgr_last_run_timestamp_seconds When each image was last pulled or built.\n",
+    );
+    out.push_str("# TYPE podman_compose_mgr_last_run_timestamp_seconds gauge\n");
+    for ((image, action), time) in &metrics.last_run {
+        out.push_str(&format!(
+            "podman_compose_mgr_last_run_timestamp_seconds{{image=\"{}\",action=\"{}\"}} {}\n",
+            label(image),
+            label(action),
+            time
+        ));
+    }
+    out
+}
+
+/// Escape a label value as the text format wants.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,91 @@
+use crate::args::Args;
+use crate::helpers::color_helper_fns as color;
+use crate::repocheck;
+
+use std::collections::BTreeMap;
+use std::net::ToSocketAddrs;
+use std::thread;
+use std::time::Duration;
+
+/// How long a registry gets to answer before it counts as unreachable.
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The registry an image is pulled from: its first path component when that looks like a host,
+/// else docker.io.
+pub fn registry_of(image: &str) -> String {
+    match image.split_once('/') {
+        Some((first, _)) if first.contains('.') || first.contains(':') || first == "localhost" => {
+            first.to_string()
+        }
+        _ => "docker.io".to_string(),
+    }
+}
+
+/// Resolve and ping each registry the images under the scan roots come from, all at once, and
+/// warn about those that don't answer.
+pub fn preflight(args: &Args) {
+    let mut registries: BTreeMap<String, usize> = BTreeMap::new();
+    for image in repocheck::image_users(args).keys() {
+        let registry = registry_of(image);
+        // nothing to pull from a local image's registry
+        if registry != "localhost" && !registry.starts_with("localhost:") {
+            *registries.entry(registry).or_insert(0) += 1;
+        }
+    }
+    let results: Vec<(&String, &usize, Result<(), String>)> = thread::scope(|s| {
+        let handles: Vec<_> = registries
+            .iter()
+            .map(|(registry, images)| (registry, images, s.spawn(|| ping(registry))))
+            .collect();
+        handles
+            .into_iter()
+            .map(|(registry, images, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err("check panicked".to_string()));
+                (registry, images, result)
+            })
+            .collect()
+    });
+
+    for (registry, images, result) in results {
+        match result {
+            Ok(()) => {
+                if args.verbose {
+                    println!("Registry {} is reachable.", registry);
+                }
+            }
+            Err(e) => {
+                color::warn(format!(
+                    "registry {} ({} image(s)) {}; pulls from it will fail.",
+                    registry, images, e
+                ));
+            }
+        }
+    }
+}
+
+/// Any HTTP answer from the registry's `/v2/` endpoint counts, a 401 included.
+fn ping(registry: &str) -> Result<(), String> {
+    let host = match registry {
+        "docker.io" => "registry-1.docker.io",
+        registry => registry,
+    };
+    let with_port = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{}:443", host)
+    };
+    with_port
+        .to_socket_addrs()
+        .map_err(|e| format!("doesn't resolve ({})", e))?;
+    let client = reqwest::blocking::Client::builder()
+        .timeout(PING_TIMEOUT)
+        .build()
+        .map_err(|e| format!("couldn't be checked ({})", e))?;
+    client
+        .get(format!("https://{}/v2/", host))
+        .send()
+        .map(|_| ())
+        .map_err(|e| format!("is unreachable ({})", e))
+}
@@ -0,0 +1,222 @@
+use crate::args::{parse_duration, Args, TimeFormat};
+
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A project file (`pcm-project.toml`) groups the scan roots and settings for a set of compose repos,
+/// so a multi-repo setup can be run with just `--project`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Project {
+    pub name: Option<String>,
+    /// Directories to scan, relative paths are resolved against the project file's dir
+    #[serde(default)]
+    pub roots: Vec<PathBuf>,
+    #[serde(default)]
+    pub exclude_path_patterns: Vec<String>,
+    #[serde(default)]
+    pub include_path_patterns: Vec<String>,
+    #[serde(default)]
+    pub build_args: Vec<String>,
+    pub registry: Option<RegistrySettings>,
+    pub secrets: Option<SecretsSettings>,
+    /// Dir for exports and reports, relative paths are resolved against the project file's dir
+    pub output_dir: Option<PathBuf>,
+    pub time_format: Option<TimeFormat>,
+    pub build_cache: Option<BuildCacheSettings>,
+    /// Same as --build-timeout, e.g. "45m"
+    pub build_timeout: Option<String>,
+    /// `[profile.<name>]` tables, selected with --profile
+    #[serde(default)]
+    pub profile: HashMap<String, ProfileSettings>,
+}
+
+/// Same keys as the top level of the project file; any that are set replace the top-level value.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileSettings {
+    pub roots: Option<Vec<PathBuf>>,
+    pub exclude_path_patterns: Option<Vec<String>>,
+    pub include_path_patterns: Option<Vec<String>>,
+    pub build_args: Option<Vec<String>>,
+    pub registry: Option<RegistrySettings>,
+    pub secrets: Option<SecretsSettings>,
+    pub output_dir: Option<PathBuf>,
+    pub time_format: Option<TimeFormat>,
+    pub build_cache: Option<BuildCacheSettings>,
+    pub build_timeout: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RegistrySettings {
+    /// Passed to podman as REGISTRY_AUTH_FILE
+    pub auth_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildCacheSettings {
+    /// Same as --build-cache
+    #[serde(rename = "ref")]
+    pub cache_ref: Option<String>,
+    /// Checked in order before `ref`, the first whose path_regex matches the compose dir wins
+    #[serde(default)]
+    pub dirs: Vec<BuildCacheDir>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuildCacheDir {
+    pub path_regex: String,
+    /// An empty ref builds without the cache
+    #[serde(rename = "ref")]
+    pub cache_ref: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SecretsSettings {
+    pub input_json: Option<PathBuf>,
+    pub output_json: Option<PathBuf>,
+}
+
+pub fn load(file: &Path, profile: Option<&str>) -> Result<Project, String> {
+    let content = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read project file '{}': {}", file.display(), e))?;
+    let mut project: Project = toml::from_str(&content)
+        .map_err(|e| format!("Failed to parse project file '{}': {}", file.display(), e))?;
+    if let Some(profile_name) = profile {
+        let selected = project.profile.get(profile_name).cloned().ok_or(format!(
+            "Profile '{}' not found in project file '{}'",
+            profile_name,
+            file.display()
+        ))?;
+        project.merge_profile(selected);
+    }
+
+    let base_dir = file.parent().unwrap_or(Path::new("."));
+    let resolve = |p: &PathBuf| {
+        if p.is_relative() {
+            base_dir.join(p)
+        } else {
+            p.clone()
+        }
+    };
+    project.roots = project.roots.iter().map(resolve).collect();
+    for root in &project.roots {
+        if !root.is_dir() {
+            return Err(format!(
+                "Project root '{}' is not a readable dir.",
+                root.display()
+            ));
+        }
+    }
+    if let Some(registry) = project.registry.as_mut() {
+        registry.auth_file = registry.auth_file.as_ref().map(resolve);
+    }
+    if let Some(secrets) = project.secrets.as_mut() {
+        secrets.input_json = secrets.input_json.as_ref().map(resolve);
+        secrets.output_json = secrets.output_json.as_ref().map(resolve);
+    }
+    project.output_dir = project.output_dir.as_ref().map(resolve);
+    for dir in project.build_cache.iter().flat_map(|c| c.dirs.iter()) {
+        Regex::new(&dir.path_regex).map_err(|e| {
+            format!(
+                "Invalid build_cache path_regex '{}' in '{}': {}",
+                dir.path_regex,
+                file.display(),
+                e
+            )
+        })?;
+    }
+    if let Some(timeout) = &project.build_timeout {
+        parse_duration(timeout)
+            .map_err(|e| format!("Invalid build_timeout in '{}': {}", file.display(), e))?;
+    }
+    Ok(project)
+}
+
+impl Project {
+    fn merge_profile(&mut self, profile: ProfileSettings) {
+        if let Some(roots) = profile.roots {
+            self.roots = roots;
+        }
+        if let Some(patterns) = profile.exclude_path_patterns {
+            self.exclude_path_patterns = patterns;
+        }
+        if let Some(patterns) = profile.include_path_patterns {
+            self.include_path_patterns = patterns;
+        }
+        if let Some(build_args) = profile.build_args {
+            self.build_args = build_args;
+        }
+        if profile.registry.is_some() {
+            self.registry = profile.registry;
+        }
+        if profile.secrets.is_some() {
+            self.secrets = profile.secrets;
+        }
+        if profile.output_dir.is_some() {
+            self.output_dir = profile.output_dir;
+        }
+        if profile.time_format.is_some() {
+            self.time_format = profile.time_format;
+        }
+        if profile.build_cache.is_some() {
+            self.build_cache = profile.build_cache;
+        }
+        if profile.build_timeout.is_some() {
+            self.build_timeout = profile.build_timeout;
+        }
+    }
+
+    /// Fill in args from the project. Patterns and build args are added to what was passed on the cmd line,
+    /// single-valued settings only apply if they weren't passed on the cmd line.
+    pub fn apply_to(&self, args: &mut Args) {
+        args.exclude_path_patterns
+            .extend(self.exclude_path_patterns.iter().cloned());
+        args.include_path_patterns
+            .extend(self.include_path_patterns.iter().cloned());
+        args.build_args.extend(self.build_args.iter().cloned());
+
+        if let Some(secrets) = &self.secrets {
+            if args.secret_mode_input_json.is_none() {
+                args.secret_mode_input_json = secrets.input_json.clone();
+            }
+            if args.secret_mode_output_json.is_none() {
+                args.secret_mode_output_json = secrets.output_json.clone();
+            }
+        }
+
+        if args.output_dir.is_none() {
+            args.output_dir = self.output_dir.clone();
+        }
+        if args.time_format.is_none() {
+            args.time_format = self.time_format;
+        }
+        if args.build_cache.is_none() {
+            args.build_cache = self.build_cache.as_ref().and_then(|c| c.cache_ref.clone());
+        }
+        if args.build_timeout.is_none() {
+            // checked when the project was loaded
+            args.build_timeout = self
+                .build_timeout
+                .as_deref()
+                .and_then(|t| parse_duration(t).ok());
+        }
+
+        if let Some(auth_file) = self.registry.as_ref().and_then(|r| r.auth_file.as_ref()) {
+            if std::env::var_os("REGISTRY_AUTH_FILE").is_none() {
+                std::env::set_var("REGISTRY_AUTH_FILE", auth_file);
+            }
+        }
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or("(unnamed project)")
+    }
+}
@@ -0,0 +1,306 @@
+use crate::args::Args;
+use crate::compose::{self, MountSource};
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::diff_helper_fns as diff;
+use crate::read_val;
+
+use serde_yaml::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{self, Path};
+
+/// Write quadlet units for each service of a compose file to --quadlet-dir: a `.container` per
+/// service, plus a `.volume` per named volume and a `.network` per network it uses. Each file is
+/// shown as a diff against what's there and written once confirmed, or right away with --yes.
+pub fn convert(compose_file: &Path, args: &Args) {
+    // bind mounts and env files need absolute paths under systemd
+    let compose_file = &path::absolute(compose_file).unwrap_or_else(|_| compose_file.to_path_buf());
+    let yaml = match compose::read_compose(compose_file) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return;
+        }
+    };
+    let quadlet_dir = args.quadlet_dir.as_ref().unwrap();
+    // file name -> content, so a volume or network several services share is written once
+    let mut units: BTreeMap<String, String> = BTreeMap::new();
+    let project = compose::project_name(compose_file, &yaml);
+    for (service, config) in compose::services(&yaml) {
+        if let Err(e) = add_units(compose_file, &yaml, &project, &service, config, &mut units) {
+            eprintln!(
+                "Skipping service `{}` in {}: {}",
+                service,
+                compose_file.display(),
+                e
+            );
+        }
+    }
+    for (file_name, content) in &units {
+        review_and_write(&quadlet_dir.join(file_name), content, args.yes);
+    }
+}
+
+fn add_units(
+    compose_file: &Path,
+    yaml: &Value,
+    project: &str,
+    service: &str,
+    config: &Value,
+    units: &mut BTreeMap<String, String>,
+) -> Result<(), String> {
+    let compose_dir = compose_file.parent().unwrap_or(Path::new("."));
+    let vars = compose::interpolation_vars(compose_file, config);
+    let interpolate = |s: &str| compose::interpolate(s, &vars).0;
+    let (image, _) = compose::resolved_image(compose_file, config)
+        .ok_or("it has no image, and a quadlet needs one to run")?;
+    let container_name = config
+        .get("container_name")
+        .and_then(|c| c.as_str())
+        .map(interpolate)
+        .unwrap_or_else(|| service.to_string());
+
+    let mut container = vec![
+        format!("ContainerName={}", container_name),
+        format!("Image={}", image),
+    ];
+    for port in sequence(config.get("ports")) {
+        if let Some(port) = publish_port(port) {
+            container.push(format!("PublishPort={}", interpolate(&port)));
+        }
+    }
+    for mount in compose::mounts(compose_file, config) {
+        match &mount.source {
+            MountSource::Tmpfs => container.push(format!("Tmpfs={}", mount.target)),
+            MountSource::Volume(name) => {
+                // the name compose gave it, so the unit finds the data compose left there
+                let volume_name = compose::resource_name(yaml, "volumes", name, project);
+                units.insert(
+                    format!("{}.volume", name),
+                    format!("[Volume]\nVolumeName={}\n", volume_name),
+                );
+                container.push(format!("Volume={}", volume_spec(&mount)));
+            }
+            _ => container.push(format!("Volume={}", volume_spec(&mount))),
+        }
+    }
+    for env in environment(config.get("environment")) {
+        container.push(format!("Environment={}", quote(&interpolate(&env))));
+    }
+    for env_file in string_or_sequence(config.get("env_file")) {
+        container.push(format!(
+            "EnvironmentFile={}",
+            escape_specifiers(&compose_dir.join(interpolate(&env_file)).to_string_lossy())
+        ));
+    }
+    if config.get("network_mode").and_then(|n| n.as_str()) == Some("host") {
+        container.push("Network=host".to_string());
+    }
+    for network in networks(config.get("networks")) {
+        let network_name = compose::resource_name(yaml, "networks", &network, project);
+        units.insert(
+            format!("{}.network", network),
+            format!("[Network]\nNetworkName={}\n", network_name),
+        );
+        container.push(format!("Network={}.network", network));
+    }
+
+    let restart = match config.get("restart").and_then(|r| r.as_str()) {
+        Some("always") | Some("unless-stopped") => Some("always"),
+        Some(policy) if policy.starts_with("on-failure") => Some("on-failure"),
+        _ => None,
+    };
+
+    let mut unit = format!(
+        "# Generated by podman-compose-mgr from {}, service `{}`\n[Unit]\nDescription={}\n\n[Container]\n{}\n",
+        compose_file.display(),
+        service,
+        service,
+        container.join("\n")
+    );
+    if let Some(restart) = restart {
+        unit.push_str(&format!("\n[Service]\nRestart={}\n", restart));
+    }
+    // compose brings every service up, not just the ones that restart
+    unit.push_str("\n[Install]\nWantedBy=default.target\n");
+    units.insert(format!("{}.container", container_name), unit);
+    Ok(())
+}
+
+fn sequence(value: Option<&Value>) -> &[Value] {
+    match value {
+        Some(Value::Sequence(seq)) => seq,
+        _ => &[],
+    }
+}
+
+fn string_or_sequence(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(s)) => vec![s.clone()],
+        // long syntax env_file entries are maps with a path key
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| {
+                v.as_str()
+                    .or_else(|| v.get("path").and_then(|p| p.as_str()))
+            })
+            .map(|s| s.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// `[ip:]host:container[/proto]` as podman takes it, from either port syntax.
+fn publish_port(port: &Value) -> Option<String> {
+    match port {
+        Value::Mapping(_) => {
+            let target = scalar(port.get("target")?)?;
+            let mut spec = match port.get("published").and_then(scalar) {
+                Some(published) => format!("{}:{}", published, target),
+                None => target,
+            };
+            if let Some(ip) = port.get("host_ip").and_then(scalar) {
+                spec = format!("{}:{}", ip, spec);
+            }
+            if let Some(protocol) = port.get("protocol").and_then(scalar) {
+                spec = format!("{}/{}", spec, protocol);
+            }
+            Some(spec)
+        }
+        port => scalar(port),
+    }
+}
+
+/// The `Volume=` value for a mount that isn't tmpfs: bind sources absolute, named volumes as their
+/// `.volume` unit.
+fn volume_spec(mount: &compose::ServiceMount) -> String {
+    let mut options = mount.options.clone();
+    if mount.read_only {
+        options.insert(0, "ro".to_string());
+    }
+    let options = if options.is_empty() {
+        String::new()
+    } else {
+        format!(":{}", options.join(","))
+    };
+    match &mount.source {
+        MountSource::Bind(path) => format!(
+            "{}:{}{}",
+            escape_specifiers(&path.to_string_lossy()),
+            mount.target,
+            options
+        ),
+        MountSource::Volume(name) => format!("{}.volume:{}{}", name, mount.target, options),
+        MountSource::Anonymous | MountSource::Tmpfs => format!("{}{}", mount.target, options),
+    }
+}
+
+/// `KEY=value` entries from either environment syntax. Keys with no value, which compose passes
+/// through from the host, have nothing to pass through from under systemd, so they're left out.
+fn environment(value: Option<&Value>) -> Vec<String> {
+    match value {
+        Some(Value::Mapping(map)) => map
+            .iter()
+            .filter_map(|(k, v)| Some(format!("{}={}", k.as_str()?, scalar(v)?)))
+            .collect(),
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| v.as_str())
+            .filter(|v| v.contains('='))
+            .map(|v| v.to_string())
+            .collect(),
+        _ => vec![],
+    }
+}
+
+/// Network names from either syntax, without compose's implicit `default`.
+fn networks(value: Option<&Value>) -> Vec<String> {
+    let names: Vec<String> = match value {
+        Some(Value::Sequence(seq)) => seq
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|v| v.to_string())
+            .collect(),
+        Some(Value::Mapping(map)) => map
+            .keys()
+            .filter_map(|k| k.as_str())
+            .map(|k| k.to_string())
+            .collect(),
+        _ => vec![],
+    };
+    names.into_iter().filter(|n| n != "default").collect()
+}
+
+/// systemd treats `%` as the start of a specifier.
+fn escape_specifiers(s: &str) -> String {
+    s.replace('%', "%%")
+}
+
+/// Quote an `Environment=` assignment the way systemd wants when it has spaces or quotes in it.
+fn quote(assignment: &str) -> String {
+    let escaped = escape_specifiers(assignment);
+    if escaped.contains(|c: char| c.is_whitespace() || c == '"' || c == '\'' || c == '\\') {
+        format!("\"{}\"", escaped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        escaped
+    }
+}
+
+fn review_and_write(target: &Path, content: &str, yes: bool) {
+    let existing = fs::read_to_string(target).ok();
+    let styled = color::stdout();
+    match &existing {
+        Some(existing) if existing == content => {
+            println!("{} is up to date.", target.display());
+            return;
+        }
+        Some(existing) => {
+            println!("Changes to {}:", target.display());
+            for line in diff::line_diff(existing, content) {
+                println!("{}", paint_diff_line(&line, styled));
+            }
+        }
+        None => {
+            println!("New {}:", target.display());
+            for line in content.lines() {
+                println!("{}", paint_diff_line(&format!("+{}", line), styled));
+            }
+        }
+    }
+    if !yes {
+        print!("Write {}? y/N: ", target.display());
+        match read_val::read_line() {
+            Ok(answer) if answer.trim().eq_ignore_ascii_case("y") => {}
+            _ => {
+                println!("Left {} as it was.", target.display());
+                return;
+            }
+        }
+    }
+    let written = target
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(target, content));
+    match written {
+        Ok(()) => println!("Wrote {}", target.display()),
+        Err(e) => eprintln!("Failed to write '{}': {}", target.display(), e),
+    }
+}
+
+/// Added lines in green and removed ones in red.
+fn paint_diff_line(line: &str, styled: bool) -> String {
+    match line.chars().next() {
+        Some('+') => color::paint(line, color::GREEN, styled),
+        Some('-') => color::paint(line, color::RED, styled),
+        _ => line.to_string(),
+    }
+}
@@ -1,8 +1,15 @@
 use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::color_helper_fns as color;
 
 use std::cmp::max;
 use std::collections::HashSet;
 use std::io::{self, Write};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// Set by --bell-after; prompts shown after this long without any input ring the terminal bell.
+static BELL_AFTER: OnceLock<Duration> = OnceLock::new();
+static LAST_INPUT: Mutex<Option<Instant>> = Mutex::new(None);
 
 pub struct Result {
     pub user_entered_val: Option<String>,
@@ -47,10 +54,34 @@ impl Default for Grammar {
     }
 }
 
+pub fn set_bell_after(secs: u64) {
+    let _ = BELL_AFTER.set(Duration::from_secs(secs));
+    *LAST_INPUT.lock().unwrap() = Some(Instant::now());
+}
+
+/// Read a line of input for a prompt that's already been printed, ringing the bell first if the run
+/// has been going unattended for longer than --bell-after.
+pub fn read_line() -> io::Result<String> {
+    if let Some(bell_after) = BELL_AFTER.get() {
+        let last_input = *LAST_INPUT.lock().unwrap();
+        if last_input.is_some_and(|t| t.elapsed() >= *bell_after) {
+            print!("\x07");
+        }
+    }
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    *LAST_INPUT.lock().unwrap() = Some(Instant::now());
+    Ok(input)
+}
+
+/// With `styled`, the image and container name are in bold. Only for printing, since the escapes would
+/// throw off the widths the prompt is fitted to the terminal with.
 fn unroll_grammer_into_string(
     grammars: &Vec<Grammar>,
     excl_if_not_in_base_prompt: bool,
     use_shortened_val: bool,
+    styled: bool,
 ) -> String {
     let mut return_result = String::new();
     // lets loop through based on the position
@@ -63,10 +94,20 @@ fn unroll_grammer_into_string(
             return_result.push_str(prefix);
         }
 
-        if use_shortened_val && grammar.shortend_val_for_prompt.is_some() {
-            return_result.push_str(grammar.shortend_val_for_prompt.as_ref().unwrap().as_str());
+        let value = if use_shortened_val && grammar.shortend_val_for_prompt.is_some() {
+            grammar.shortend_val_for_prompt.as_deref()
         } else if grammar.display_at_all {
-            return_result.push_str(grammar.original_val_for_prompt.as_ref().unwrap().as_str());
+            grammar.original_val_for_prompt.as_deref()
+        } else {
+            None
+        };
+        if let Some(value) = value {
+            let bold = styled
+                && matches!(
+                    grammar.grammer_type,
+                    GrammerType::Image | GrammerType::ContainerName
+                );
+            return_result.push_str(&color::paint(value, color::BOLD, bold));
         }
 
         if let Some(suffix) = &grammar.suffix {
@@ -100,15 +141,15 @@ pub fn read_val_from_cmd_line_and_proceed(
         grammar: Vec::new(),
     };
 
-    let refresh_static = unroll_grammer_into_string(grammars, true, false);
-    let refresh_prompt = unroll_grammer_into_string(grammars, false, false);
+    let refresh_static = unroll_grammer_into_string(grammars, true, false, false);
+    let refresh_prompt = unroll_grammer_into_string(grammars, false, false, false);
 
     // if the prompt is too long, we need to shorten some stuff.
-    // At a minimum, we'll display our 23 chars of "refresh ... from ?" stuff.
+    // At a minimum, we'll display all of the static "refresh ... from ?" stuff, choices included.
     // Then we divide remaining space equally between image name and path name.
     // We're not going to go less than 12 chars for path and image name, anything less feels like we're cutting too much off maybe.
-    // This means total display chars is 23 + 12 + 12 = 47 at a min
-    // if user has less than 47 wide, well then we'll have to let the terminal word-wrap.
+    // This means total display chars is the static part + 12 + 12 (plus the "..."s) at a min
+    // if user has less than that wide, well then we'll have to let the terminal word-wrap.
     let term_width = cmd::get_terminal_display_width();
     // println!("term_width: {}", term_width);
     // println!("refresh_prompt len: {}", refresh_prompt.len());
@@ -117,10 +158,11 @@ pub fn read_val_from_cmd_line_and_proceed(
     let mut type_2_shortened = type_2_to_shorten.clone();
     // let image_orig = image.to_string();
     // 1 char for a little buffer so it doesnt wrap after user input
-    if refresh_prompt.len() > term_width - 1 {
+    if refresh_prompt.len() > term_width.saturating_sub(1) {
         let truncated_symbols = "...";
+        let static_width = refresh_static.len() + 2 * truncated_symbols.len() + 1;
         let mut max_avail_chars_for_image_and_path =
-            max(term_width, 47) - refresh_static.len() - 2 * truncated_symbols.len() - 1;
+            max(term_width, static_width + 12 + 12).saturating_sub(static_width);
         if max_avail_chars_for_image_and_path % 2 != 0 {
             max_avail_chars_for_image_and_path -= 1;
         }
@@ -186,7 +228,10 @@ pub fn read_val_from_cmd_line_and_proceed(
         }
     });
 
-    print!("{}", unroll_grammer_into_string(grammars, false, true));
+    print!(
+        "{}",
+        unroll_grammer_into_string(grammars, false, true, color::stdout())
+    );
 
     let user_choices: HashSet<String> = grammars
         .iter()
@@ -197,9 +242,7 @@ pub fn read_val_from_cmd_line_and_proceed(
         .collect();
 
     loop {
-        let mut input = String::new();
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut input).unwrap();
+        let input = read_line().unwrap();
         let input = input.trim();
 
         if user_choices.contains(input) {
@@ -1,16 +1,48 @@
-use crate::args::Args;
+use crate::answers::Answers;
+use crate::args::{Args, Mode, TimeFormat};
+use crate::buildcache;
+use crate::buildfiles::BuildfileChoices;
+use crate::compose;
+use crate::dockerfilelint;
+use crate::exclusions::Exclusions;
+use crate::helpers::audit_helper_fns as audit;
 use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::dockerfile_helper_fns;
+use crate::helpers::engine_helper_fns;
+use crate::helpers::git_helper_fns;
+use crate::helpers::ignore_helper_fns;
+use crate::helpers::image_name_helper_fns as image_name;
+use crate::helpers::log_sink_helper_fns as log_sink;
 use crate::helpers::podman_helper_fns;
+use crate::history::BuildHistory;
+use crate::image_io;
+use crate::imagename;
+use crate::locks::ImageLock;
+use crate::metrics;
 use crate::read_val::{self, Grammar, GrammerType};
+use crate::repocheck;
+use crate::restartsvcs;
+use crate::scan;
+use crate::session::Session;
+use crate::state::StateDir;
+use crate::usage;
 
-// use regex::Regex;
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, SecondsFormat};
+use regex::Regex;
 use serde_yaml::Value;
+use std::collections::HashMap;
 use std::fs;
-use std::fs::File;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 use std::vec;
 use walkdir::DirEntry;
 
+/// Images created or pulled longer ago than this are shown as stale in the details.
+const STALE_AFTER_DAYS: i64 = 90;
+
 #[derive(Debug, PartialEq)]
 pub struct Image {
     pub name: Option<String>,
@@ -18,72 +50,367 @@ pub struct Image {
     pub skipall_by_this_name: bool,
 }
 
+/// A service from the effective (override merged) compose config.
+struct ComposeService<'a> {
+    name: &'a str,
+    config: &'a Value,
+    /// `VAR=value` substitutions made in the image name
+    substitutions: Vec<String>,
+    /// from the service's x-podman-compose-mgr block
+    build_args: Vec<String>,
+    build_timeout: Option<Duration>,
+}
+
 pub struct RebuildManager {
     images_checked: Vec<Image>,
+    bytes_reclaimed: u64,
+    answers: Answers,
+    reviewed: u32,
+    pulled: u32,
+    built: u32,
+    skipped: u32,
+    failed: u32,
+    /// recreated by --restart-after-build but whose healthcheck didn't pass
+    unhealthy: usize,
+    started: Instant,
+    history: Option<BuildHistory>,
+    /// shared with the scan, which skips what's excluded at the prompt from then on
+    exclusions: Option<Rc<Exclusions>>,
+    /// set by the `x` choice, so the rest of that compose file's services aren't offered
+    dir_excluded: bool,
+    buildfile_choices: Option<BuildfileChoices>,
+    /// compose and quadlet files by the images they use, built the first time details are shown
+    image_users: Option<HashMap<String, Vec<PathBuf>>>,
+    state: Option<StateDir>,
+    /// for resuming an interactive rebuild that was quit partway
+    session: Option<Session>,
+    time_format: TimeFormat,
+    /// from rebuild's positional NAME globs; empty offers everything
+    name_filters: Vec<Regex>,
 }
 
 impl RebuildManager {
-    pub fn new() -> Self {
-        Self {
+    pub fn new(args: &Args, exclusions: Option<Rc<Exclusions>>) -> Result<Self, String> {
+        // history and locks are a nicety, a broken state dir shouldn't stop a rebuild
+        let state = match StateDir::open(args) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                color::warn(format!(
+                    "not tracking build history or locking images: {}",
+                    e
+                ));
+                None
+            }
+        };
+        let history = state
+            .as_ref()
+            .and_then(|state| match BuildHistory::load(state) {
+                Ok(history) => Some(history),
+                Err(e) => {
+                    color::warn(format!("not tracking build history: {}", e));
+                    None
+                }
+            });
+        let buildfile_choices =
+            state
+                .as_ref()
+                .and_then(|state| match BuildfileChoices::load(state) {
+                    Ok(choices) => Some(choices),
+                    Err(e) => {
+                        color::warn(format!("not remembering build file choices: {}", e));
+                        None
+                    }
+                });
+        // a daemon's scans start over each time anyway, and with no terminal there's no one to ask
+        // whether to resume, so those runs leave any saved session alone for the next one that can.
+        // Nor does a run narrowed by NAME filters or --since, whose end isn't the end of the scan
+        let session = match &state {
+            Some(state)
+                if matches!(args.mode, Mode::Rebuild)
+                    && args.daemon.is_none()
+                    && args.filters.is_empty()
+                    && args.since.is_none()
+                    && io::stdin().is_terminal() =>
+            {
+                match Session::start(state, &args.scan_roots()) {
+                    Ok(session) => Some(session),
+                    Err(e) => {
+                        color::warn(format!("a quit rebuild won't be resumable: {}", e));
+                        None
+                    }
+                }
+            }
+            _ => None,
+        };
+        Ok(Self {
             images_checked: Vec::new(),
+            bytes_reclaimed: 0,
+            answers: Answers::load(args)?,
+            reviewed: 0,
+            pulled: 0,
+            built: 0,
+            skipped: 0,
+            failed: 0,
+            unhealthy: 0,
+            started: Instant::now(),
+            history,
+            exclusions,
+            dir_excluded: false,
+            buildfile_choices,
+            image_users: None,
+            state,
+            session,
+            time_format: args.time_format.unwrap_or_default(),
+            name_filters: args
+                .filters
+                .iter()
+                .map(|glob| Regex::new(&ignore_helper_fns::glob_to_regex(glob)).unwrap())
+                .collect(),
+        })
+    }
+
+    pub fn bytes_reclaimed(&self) -> u64 {
+        self.bytes_reclaimed
+    }
+
+    pub fn failed(&self) -> u32 {
+        self.failed
+    }
+
+    /// Whether the image or container is one the NAME filters ask for. An image matches by its full
+    /// name, its name without the tag, or just the last part of that, so `app-*` matches `localhost/app-web:1.2`.
+    fn wanted(&self, image: &str, container: Option<&str>, args: &Args) -> bool {
+        if self.name_filters.is_empty() {
+            return true;
+        }
+        let repository = image_name::repository_of(image);
+        let short_name = repository.rsplit('/').next().unwrap_or(repository);
+        let wanted = self.name_filters.iter().any(|filter| {
+            [Some(image), Some(repository), Some(short_name), container]
+                .into_iter()
+                .flatten()
+                .any(|name| filter.is_match(name))
+        });
+        if !wanted && args.verbose {
+            println!(
+                "Skipping {}, it doesn't match {}",
+                image,
+                args.filters.join(" ")
+            );
+        }
+        wanted
+    }
+
+    /// Whether --fail-fast or --max-failures says to stop processing images.
+    pub fn should_stop(&self, args: &Args) -> bool {
+        let limit = if args.fail_fast {
+            Some(1)
+        } else {
+            args.max_failures
+        };
+        limit.is_some_and(|limit| self.failed >= limit)
+    }
+
+    /// Went through every compose file, so there's nothing for a later run to resume.
+    pub fn finish_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            if let Err(e) = session.finish() {
+                color::warn(e);
+            }
+        }
+    }
+
+    /// Whether the run being resumed got through `key` already.
+    fn done_before(&self, key: &str, args: &Args) -> bool {
+        let done = self.session.as_ref().is_some_and(|s| s.was_done(key));
+        if done && args.verbose {
+            println!("Skipping {}, the resumed run already went through it.", key);
+        }
+        done
+    }
+
+    fn mark_done(&mut self, key: String) {
+        if let Some(session) = self.session.as_mut() {
+            if let Err(e) = session.mark_done(key) {
+                color::warn(e);
+            }
         }
     }
 
+    /// e.g. `Reviewed 12 image(s) in 4m 3s: 3 pulled, 1 built, 7 skipped, 1 failed.`
+    pub fn summary(&self) -> String {
+        let secs = self.started.elapsed().as_secs();
+        let elapsed = if secs >= 60 {
+            format!("{}m {}s", secs / 60, secs % 60)
+        } else {
+            format!("{}s", secs)
+        };
+        let unhealthy = if self.unhealthy > 0 {
+            format!(", {} service(s) unhealthy after restart", self.unhealthy)
+        } else {
+            String::new()
+        };
+        format!(
+            "Reviewed {} image(s) in {}: {} pulled, {} built, {} skipped, {} failed{}.",
+            self.reviewed, elapsed, self.pulled, self.built, self.skipped, self.failed, unhealthy
+        )
+    }
+
+    fn record_result(&mut self, image: &str, action: &str, ok: bool, started: Instant) {
+        match (ok, action) {
+            (false, _) => self.failed += 1,
+            (true, "pull") => self.pulled += 1,
+            (true, _) => self.built += 1,
+        }
+        self.record_sub_result(image, action, ok, started);
+    }
+
+    /// Metrics and history for one part of a pull or build, such as one arch of a multi-arch build,
+    /// without counting it in the run's totals.
+    fn record_sub_result(&mut self, image: &str, action: &str, ok: bool, started: Instant) {
+        metrics::record_run(image, action, ok);
+        if let Some(history) = self.history.as_mut() {
+            if let Err(e) = history.record(image, action, ok, started.elapsed()) {
+                color::warn(e);
+            }
+        }
+    }
+
+    /// Lock `image` against other runs before pulling or building it. None when another run pulled
+    /// or built it successfully while we waited, so there's nothing left to do.
+    fn lock_image(&mut self, image: &str) -> Option<ImageLock> {
+        let state = match &self.state {
+            Some(state) => state,
+            None => return Some(ImageLock::unlocked()),
+        };
+        let lock = match ImageLock::acquire(state, image) {
+            Ok(lock) => lock,
+            Err(e) => {
+                color::warn(e);
+                return Some(ImageLock::unlocked());
+            }
+        };
+        if let (Some(since), Some(history)) = (lock.waited_since, self.history.as_mut()) {
+            if let Err(e) = history.reload() {
+                color::warn(e);
+            }
+            if let Some(last) = history.last(image).filter(|l| l.ok && l.ts >= since) {
+                println!(
+                    "Another run finished a {} of {} while we waited, skipping it.",
+                    last.action, image
+                );
+                self.skipped += 1;
+                return None;
+            }
+        }
+        Some(lock)
+    }
+
     pub fn rebuild(&mut self, entry: &DirEntry, args: &Args) {
-        let yaml = self.read_yaml_file(entry.path().to_str().unwrap());
-        if let Some(services) = yaml.get("services") {
-            if let Some(services_map) = services.as_mapping() {
-                for (_, service_config) in services_map {
-                    // println!("Service: {:?}", service_name);
-                    if let Some(image) = service_config.get("image") {
-                        // println!("  Image: {:?}", image);
-                        if let Some(container_name) = service_config.get("container_name") {
-                            let image_string = image.as_str().unwrap().to_string();
-                            let container_nm_string = container_name.as_str().unwrap().to_string();
-
-                            // if this image is in the vec as a skippable image, skip this iter entry (aka continue)
-                            let img_is_set_to_skip = self.images_checked.iter().any(|i| {
-                                if let Some(ref name) = i.name {
-                                    name == &image_string && i.skipall_by_this_name
+        let yaml = match compose::read_compose(entry.path()) {
+            Ok(yaml) => yaml,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                return;
+            }
+        };
+        let mut services = vec![];
+        for (service_name, service_config) in compose::services(&yaml) {
+            match compose::service_meta(service_config) {
+                Ok(meta) => services.push((service_name, service_config, meta)),
+                Err(e) => eprintln!(
+                    "Error: service `{}` in {}: {}",
+                    service_name,
+                    entry.path().display(),
+                    e
+                ),
+            }
+        }
+        // stable, so equal priorities keep their file order
+        services.sort_by_key(|(_, _, meta)| std::cmp::Reverse(meta.priority));
+        for (service_name, service_config, meta) in services {
+            if self.should_stop(args) {
+                return;
+            }
+            if meta.skip {
+                if args.verbose {
+                    println!(
+                        "Skipping service `{}`, it has skip: true in {}",
+                        service_name,
+                        compose::META_KEY
+                    );
+                }
+                continue;
+            }
+            // println!("Service: {:?}", service_name);
+            if let Some((image_string, substitutions)) =
+                compose::resolved_image(entry.path(), service_config)
+            {
+                // println!("  Image: {:?}", image);
+                if let Some(container_name) = service_config.get("container_name") {
+                    let vars = compose::interpolation_vars(entry.path(), service_config);
+                    let container_nm_string =
+                        compose::interpolate(container_name.as_str().unwrap(), &vars).0;
+                    if !self.wanted(&image_string, Some(&container_nm_string), args) {
+                        continue;
+                    }
+
+                    // if this image is in the vec as a skippable image, skip this iter entry (aka continue)
+                    let img_is_set_to_skip = self.images_checked.iter().any(|i| {
+                        if let Some(ref name) = i.name {
+                            name == &image_string && i.skipall_by_this_name
+                        } else {
+                            false
+                        }
+                    });
+
+                    // or, if this image is not in the list of images we've already checked, continue
+                    let img_and_container_previously_reviewed =
+                        self.images_checked.iter().any(|i| {
+                            if let Some(ref name) = i.name {
+                                if let Some(ref contner) = i.container {
+                                    name == &image_string && contner == &container_nm_string
                                 } else {
                                     false
                                 }
-                            });
-
-                            // or, if this image is not in the list of images we've already checked, continue
-                            let img_and_container_previously_reviewed =
-                                self.images_checked.iter().any(|i| {
-                                    if let Some(ref name) = i.name {
-                                        if let Some(ref contner) = i.container {
-                                            name == &image_string && contner == &container_nm_string
-                                        } else {
-                                            false
-                                        }
-                                    } else {
-                                        false
-                                    }
-                                });
-
-                            // image ck is only empty on first check, so as long as we're non-empty, we might skip this image_string, move to next test
-                            if !self.images_checked.is_empty()
-                                && (img_is_set_to_skip || img_and_container_previously_reviewed)
-                            {
-                                continue;
                             } else {
-                                self.read_val_loop(
-                                    entry,
-                                    &image_string,
-                                    &args.build_args,
-                                    &container_nm_string,
-                                );
-
-                                let c = Image {
-                                    name: Some(image_string),
-                                    container: Some(container_nm_string),
-                                    skipall_by_this_name: false,
-                                };
-                                self.images_checked.push(c);
+                                false
                             }
+                        });
+
+                    // image ck is only empty on first check, so as long as we're non-empty, we might skip this image_string, move to next test
+                    let session_key = format!("{} {}", image_string, container_nm_string);
+                    if (!self.images_checked.is_empty()
+                        && (img_is_set_to_skip || img_and_container_previously_reviewed))
+                        || self.done_before(&session_key, args)
+                        || self.is_fresh(&image_string, args)
+                    {
+                        continue;
+                    } else {
+                        let service = ComposeService {
+                            name: &service_name,
+                            config: service_config,
+                            substitutions,
+                            build_timeout: meta.build_timeout(),
+                            build_args: meta.build_args,
+                        };
+                        self.read_val_loop(
+                            entry,
+                            &image_string,
+                            args,
+                            &container_nm_string,
+                            &service,
+                        );
+                        self.mark_done(session_key);
+
+                        let c = Image {
+                            name: Some(image_string),
+                            container: Some(container_nm_string),
+                            skipall_by_this_name: false,
+                        };
+                        self.images_checked.push(c);
+                        if std::mem::take(&mut self.dir_excluded) {
+                            return;
                         }
                     }
                 }
@@ -91,13 +418,111 @@ impl RebuildManager {
         }
     }
 
+    /// A build file with no compose or quadlet file next to it, built as --image-name-template
+    /// names it. There's nothing upstream to pull, so building is the only action.
+    pub fn rebuild_buildfile(&mut self, entry: &DirEntry, args: &Args) {
+        let Some(template) = &args.image_name_template else {
+            return;
+        };
+        let dir = entry.path().parent().unwrap();
+        let Some(buildfile) = self.buildfile(dir) else {
+            return;
+        };
+        let image = imagename::from_template(template, &buildfile);
+        if !self.wanted(&image, None, args) {
+            return;
+        }
+        let img_is_set_to_skip = self
+            .images_checked
+            .iter()
+            .any(|i| i.skipall_by_this_name && i.name.as_deref() == Some(image.as_str()));
+        if img_is_set_to_skip || self.done_before(&image, args) || self.is_fresh(&image, args) {
+            return;
+        }
+        self.reviewed += 1;
+        let prompt = format!("Build {} from {}?", image, buildfile.display());
+
+        // a pre-supplied answer other than b or B skips it, since p has nothing to pull
+        let mut preset_answer = self.answers.answer_for(&image).map(|(answer, source)| {
+            let answer = if answer == "b" || answer == "B" {
+                answer
+            } else {
+                "N".to_string()
+            };
+            println!("{} {} (from {})", prompt, answer, source);
+            answer
+        });
+        loop {
+            let answer = match preset_answer.take() {
+                Some(answer) => answer,
+                None => {
+                    print!("{} b/B/f/N/?: ", prompt);
+                    match read_val::read_line() {
+                        Ok(input) if !input.is_empty() => input.trim().to_string(),
+                        _ => break,
+                    }
+                }
+            };
+            match answer.as_str() {
+                "b" | "B" => {
+                    let Some(_lock) = self.lock_image(&image) else {
+                        break;
+                    };
+                    let started = Instant::now();
+                    log_sink::set_context(&image, "build");
+                    let result = self.build_image_from_dockerfile(
+                        entry,
+                        &image,
+                        args.build_args.iter().map(|s| s.as_str()).collect(),
+                        args,
+                        args.no_cache || answer == "B",
+                        args.build_timeout,
+                    );
+                    log_sink::clear_context();
+                    let built = result.is_ok();
+                    self.record_result(&image, "build", built, started);
+                    audit::record(args, "build", &image, build_outcome(&result));
+                    if built && args.scan_after_build {
+                        scan::scan_image(&image, args);
+                    }
+                    if built && args.prune_after_build {
+                        self.prune_images(args);
+                    }
+                    break;
+                }
+                "f" => {
+                    self.show_buildfile(entry);
+                }
+                "N" => {
+                    self.skipped += 1;
+                    break;
+                }
+                "?" => {
+                    println!("b = Build the image from this build file.");
+                    println!("B = Build like b, without the layer cache.");
+                    println!(
+                        "f = Show the Dockerfile/Containerfile, with its instructions highlighted."
+                    );
+                    println!("N = Do nothing, skip this build file.");
+                    println!("? = Display this help.");
+                }
+                _ => {
+                    println!("Invalid input. Please enter b/B/f/N/?: ");
+                }
+            }
+        }
+        self.mark_done(image);
+    }
+
     fn read_val_loop(
         &mut self,
         entry: &DirEntry,
         image: &str,
-        build_args: &Vec<String>,
+        args: &Args,
         container_name: &str,
+        service: &ComposeService,
     ) {
+        self.reviewed += 1;
         let mut grammars: Vec<Grammar> = vec![];
 
         let grm1 = Grammar {
@@ -167,7 +592,9 @@ impl RebuildManager {
         };
         grammars.push(grm5);
 
-        let choices = vec!["p", "N", "d", "b", "s", "?"];
+        let choices = vec![
+            "p", "N", "d", "D", "f", "h", "u", "b", "B", "e", "T", "P", "v", "s", "x", "?",
+        ];
         for i in 0..choices.len() {
             let mut choice_separator = Some("/".to_string());
             if i == choices.len() - 1 {
@@ -186,12 +613,26 @@ impl RebuildManager {
             grammars.push(choice_grammar);
         }
 
+        // a pre-supplied answer is applied once, in place of the first prompt
+        let mut preset_answer = self.answers.answer_for(image);
         loop {
-            let result = read_val::read_val_from_cmd_line_and_proceed(
-                &mut grammars,
-                GrammerType::DockerComposePath,
-                GrammerType::Image,
-            );
+            let result = match preset_answer.take() {
+                Some((answer, source)) => {
+                    println!(
+                        "Refresh {} from {}? {} (from {})",
+                        image, docker_compose_pth_fmtted, answer, source
+                    );
+                    read_val::Result {
+                        user_entered_val: Some(answer),
+                        grammar: vec![],
+                    }
+                }
+                None => read_val::read_val_from_cmd_line_and_proceed(
+                    &mut grammars,
+                    GrammerType::DockerComposePath,
+                    GrammerType::Image,
+                ),
+            };
 
             match result.user_entered_val {
                 None => {
@@ -199,20 +640,94 @@ impl RebuildManager {
                 }
                 Some(user_entered_val) => match user_entered_val.as_str() {
                     "p" => {
-                        self.pull_it(image);
+                        let Some(_lock) = self.lock_image(image) else {
+                            break;
+                        };
+                        let started = Instant::now();
+                        log_sink::set_context(image, "pull");
+                        let pulled = self.pull_it(image);
+                        log_sink::clear_context();
+                        self.record_result(image, "pull", pulled, started);
+                        audit::record(args, "pull", image, audit::outcome(pulled));
+                        if pulled && args.scan_after_build {
+                            scan::scan_image(image, args);
+                        }
+                        if pulled && args.restart_after_build {
+                            self.unhealthy +=
+                                restartsvcs::restart_after_build(entry.path(), image, args);
+                        }
                         break;
                     }
                     "N" => {
+                        self.skipped += 1;
                         break;
                     }
+                    "D" => {
+                        self.diff_dockerfile_against_history(entry, image);
+                    }
+                    "f" => {
+                        self.show_buildfile(entry);
+                    }
+                    "h" => {
+                        self.show_image_history(image, args);
+                    }
+                    "e" => {
+                        image_io::export_image(image, args);
+                    }
+                    "T" => {
+                        image_io::tag_image(image, args);
+                    }
+                    "P" => {
+                        image_io::push_image(image, args);
+                    }
+                    "v" => {
+                        scan::scan_image(image, args);
+                    }
+                    "u" => {
+                        self.show_usage(image, args);
+                    }
                     "d" | "?" => match user_entered_val.as_str() {
                         "d" => {
                             println!("Image: {}", image.to_string());
+                            if !service.substitutions.is_empty() {
+                                println!("Image variables: {}", service.substitutions.join(", "));
+                            }
                             println!("Container name: {}", container_name);
+                            let depends_on = compose::depends_on(service.config);
+                            if !depends_on.is_empty() {
+                                println!("Depends on: {}", depends_on.join(", "));
+                            }
+                            if !service.build_args.is_empty() {
+                                println!(
+                                    "Build args from {}: {}",
+                                    compose::META_KEY,
+                                    service.build_args.join(" ")
+                                );
+                            }
                             println!("Compose file: {}", docker_compose_pth_fmtted);
+                            if let Some(override_file) = compose::override_file(entry.path()) {
+                                let keys = compose::overridden_keys(entry.path(), service.name);
+                                if !keys.is_empty() {
+                                    println!(
+                                        "Overridden by {}: {}",
+                                        override_file.display(),
+                                        keys.join(", ")
+                                    );
+                                }
+                            }
+                            self.print_devices(service.config);
+                            self.print_mounts(entry.path(), service.config);
+                            self.print_usage(entry.path(), image, args);
+                            for problem in restartsvcs::rootless_problems(service.config) {
+                                println!(
+                                    "{} needs root but podman is rootless: {}",
+                                    color::paint("Warning:", color::YELLOW, color::stdout()),
+                                    problem
+                                );
+                            }
                             println!(
                                 "Created: {}",
-                                self.format_time_ago(
+                                self.format_age(
                                     podman_helper_fns::get_podman_image_upstream_create_time(
                                         &image
                                     )
@@ -221,22 +736,37 @@ impl RebuildManager {
                             );
                             println!(
                                 "Pulled: {}",
-                                self.format_time_ago(
+                                self.format_age(
                                     podman_helper_fns::get_podman_ondisk_modify_time(&image)
                                         .unwrap()
                                 )
                             );
-                            println!(
-                                "Dockerfile exists: {}",
-                                cmd::dockerfile_exists_and_readable(
-                                    &entry
-                                        .path()
-                                        .parent()
-                                        .unwrap()
-                                        .join("Dockerfile")
-                                        .to_path_buf()
-                                )
-                            );
+                            if let Some(last) =
+                                self.history.as_ref().and_then(|h| h.last(image)).cloned()
+                            {
+                                println!(
+                                    "Last {}: {} ({})",
+                                    last.action,
+                                    self.format_time(last.time()),
+                                    last.outcome()
+                                );
+                            }
+                            if let Ok(labels) = podman_helper_fns::get_podman_image_labels(image) {
+                                if let Some(revision) = labels.get(git_helper_fns::REVISION_LABEL) {
+                                    println!("Built from commit: {}", revision);
+                                }
+                                if let Some(source) = labels.get(git_helper_fns::SOURCE_LABEL) {
+                                    println!("Source: {}", source);
+                                }
+                            }
+                            match self.chosen_buildfile(entry.path().parent().unwrap()) {
+                                Some(buildfile) => println!(
+                                    "Build file: {} (readable: {})",
+                                    buildfile.display(),
+                                    cmd::dockerfile_exists_and_readable(&buildfile)
+                                ),
+                                None => println!("Build file: none"),
+                            }
                         }
                         "?" => {
                             println!("p = Pull image from upstream.");
@@ -245,21 +775,86 @@ impl RebuildManager {
                                         "d = Display info (image name, docker-compose.yml path, upstream img create date, and img on-disk modify date)."
                                     );
                             println!(
-                                        "b = Build image from the Dockerfile residing in same path as the docker-compose.yml."
+                                        "D = Diff the Dockerfile/Containerfile's instructions against the image's build history."
                                     );
+                            println!(
+                                        "f = Show the Dockerfile/Containerfile, with its instructions highlighted."
+                                    );
+                            println!(
+                                        "h = Show the image's layers as a timeline: age, size and the command that made each."
+                                    );
+                            println!(
+                                        "u = Show every compose and .container file using this image, under any tag."
+                                    );
+                            println!(
+                                        "b = Build image from the Dockerfile or Containerfile residing in same path as the docker-compose.yml."
+                                    );
+                            println!("B = Build image like b, without the layer cache.");
+                            println!("e = Export image to a tarball with podman save.");
+                            println!("T = Tag image with another name.");
+                            println!("P = Push image to a registry.");
+                            println!("v = Scan image for vulnerabilities with trivy.");
                             println!(
                                         "s = Skip all subsequent images with this same name (regardless of container name)."
                                     );
+                            println!(
+                                "x = Exclude this compose file's dir from this and future scans."
+                            );
                             println!("? = Display this help.");
                         }
                         _ => {}
                     },
-                    "b" => {
-                        self.build_image_from_dockerfile(
+                    "b" | "B" => {
+                        let Some(_lock) = self.lock_image(image) else {
+                            break;
+                        };
+                        let started = Instant::now();
+                        log_sink::set_context(image, "build");
+                        let result = self.build_image_from_dockerfile(
                             &entry,
                             image,
-                            build_args.iter().map(|s| s.as_str()).collect(),
+                            args.build_args
+                                .iter()
+                                .chain(&service.build_args)
+                                .map(|s| s.as_str())
+                                .collect(),
+                            args,
+                            args.no_cache || user_entered_val == "B",
+                            service.build_timeout.or(args.build_timeout),
                         );
+                        log_sink::clear_context();
+                        let built = result.is_ok();
+                        self.record_result(image, "build", built, started);
+                        audit::record(args, "build", image, build_outcome(&result));
+                        if built && args.scan_after_build {
+                            scan::scan_image(image, args);
+                        }
+                        if built && args.restart_after_build {
+                            self.unhealthy +=
+                                restartsvcs::restart_after_build(entry.path(), image, args);
+                        }
+                        if built && args.prune_after_build {
+                            self.prune_images(args);
+                        }
+                        break;
+                    }
+                    "x" => {
+                        let dir = entry.path().parent().unwrap();
+                        match self.exclusions.as_ref().map(|x| x.add(dir)) {
+                            Some(Ok(file)) => {
+                                println!(
+                                    "Excluded {} from this and future scans, remove it from {} to undo.",
+                                    dir.display(),
+                                    file.display()
+                                );
+                                self.dir_excluded = true;
+                            }
+                            Some(Err(e)) => eprintln!("Error: {}", e),
+                            None => {
+                                eprintln!("Error: no usable state dir to save the exclusion in.")
+                            }
+                        }
+                        self.skipped += 1;
                         break;
                     }
                     "s" => {
@@ -269,84 +864,533 @@ impl RebuildManager {
                             skipall_by_this_name: true,
                         };
                         self.images_checked.push(c);
+                        self.skipped += 1;
                         break;
                     }
                     _ => {
-                        println!("Invalid input. Please enter p/N/d/b/s/?: ");
+                        println!("Invalid input. Please enter p/N/d/D/f/h/u/b/B/e/T/P/v/s/x/?: ");
                     }
                 },
             }
         }
     }
 
-    fn format_time_ago(&mut self, dt: DateTime<Local>) -> String {
-        let now = Local::now();
-        let duration = now.signed_duration_since(dt);
-        let days = duration.num_days();
-        let hours = duration.num_hours();
-        let minutes = duration.num_minutes();
-        let seconds = duration.num_seconds();
-        if days > 0 {
-            format!("{} days ago", days)
-        } else if hours > 0 {
-            format!("{} hours ago", hours)
-        } else if minutes > 0 {
-            format!("{} minutes ago", minutes)
+    /// The build file to use in `dir`. With more than one there, the one picked before, else ask
+    /// which (and remember it). Without a terminal to ask on it's the first, as `find_buildfile` has it.
+    fn buildfile(&mut self, dir: &Path) -> Option<PathBuf> {
+        let candidates = dockerfile_helper_fns::buildfile_candidates(dir);
+        if candidates.len() < 2 {
+            return candidates.into_iter().next();
+        }
+        if let Some(chosen) = self
+            .buildfile_choices
+            .as_ref()
+            .and_then(|choices| choices.get(dir))
+        {
+            return Some(chosen);
+        }
+        if !io::stdin().is_terminal() {
+            return candidates.into_iter().next();
+        }
+
+        println!("{} has {} build files:", dir.display(), candidates.len());
+        for (i, candidate) in candidates.iter().enumerate() {
+            // the last FROM is the stage that ends up in the image
+            let base = dockerfile_helper_fns::read_instructions(candidate)
+                .ok()
+                .and_then(|instructions| {
+                    instructions
+                        .into_iter()
+                        .rev()
+                        .find(|instruction| instruction.keyword == "FROM")
+                })
+                .map(|from| format!("FROM {}", from.args))
+                .unwrap_or_else(|| "no FROM".to_string());
+            let name = candidate
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            println!("  {}) {}  ({})", i + 1, name, base);
+        }
+        let chosen = loop {
+            print!("Which one? 1-{}, Enter for 1: ", candidates.len());
+            let input = match read_val::read_line() {
+                Ok(input) if !input.is_empty() => input.trim().to_string(),
+                _ => return candidates.into_iter().next(),
+            };
+            if input.is_empty() {
+                break 0;
+            }
+            match input.parse::<usize>() {
+                Ok(n) if (1..=candidates.len()).contains(&n) => break n - 1,
+                _ => println!("Invalid input. Please enter 1-{}.", candidates.len()),
+            }
+        };
+        let chosen = candidates.into_iter().nth(chosen).unwrap();
+        if let Some(choices) = self.buildfile_choices.as_mut() {
+            match choices.set(&chosen) {
+                Ok(file) => println!(
+                    "Using {} from now on (remembered in {}).",
+                    chosen.display(),
+                    file.display()
+                ),
+                Err(e) => color::warn(format!("not remembering the build file: {}", e)),
+            }
+        }
+        Some(chosen)
+    }
+
+    /// The build file picked for `dir` before, else the first there. Never asks.
+    fn chosen_buildfile(&self, dir: &Path) -> Option<PathBuf> {
+        self.buildfile_choices
+            .as_ref()
+            .and_then(|choices| choices.get(dir))
+            .or_else(|| dockerfile_helper_fns::find_buildfile(dir))
+    }
+
+    fn show_buildfile(&mut self, entry: &DirEntry) {
+        let dir = entry.path().parent().unwrap();
+        let buildfile = match self.buildfile(dir) {
+            Some(buildfile) => buildfile,
+            None => {
+                eprintln!(
+                    "No Dockerfile or Containerfile found in '{}'",
+                    dir.display()
+                );
+                return;
+            }
+        };
+        match fs::read_to_string(&buildfile) {
+            Ok(content) => {
+                println!("{}:", buildfile.display());
+                for line in dockerfile_helper_fns::highlighted_lines(&content, color::stdout()) {
+                    println!("{}", line);
+                }
+                for finding in dockerfilelint::lint_buildfile(&buildfile) {
+                    println!(
+                        "  {}: {}: {}",
+                        finding.line, finding.severity, finding.message
+                    );
+                }
+            }
+            Err(e) => eprintln!("Failed to read '{}': {}", buildfile.display(), e),
+        }
+    }
+
+    /// Oldest layer first. Commands are cut to the terminal width unless --verbose, which prints
+    /// them whole.
+    fn show_image_history(&mut self, image: &str, args: &Args) {
+        let layers = match podman_helper_fns::get_podman_image_layers(image) {
+            Ok(layers) => layers,
+            Err(e) => {
+                eprintln!("Error reading history of {}: {}", image, e);
+                return;
+            }
+        };
+        let ages: Vec<String> = layers
+            .iter()
+            .map(|layer| match layer.created {
+                Some(created) => self.format_time(created),
+                None => "-".to_string(),
+            })
+            .collect();
+        let age_width = ages.iter().map(|a| a.len()).max().unwrap_or(0).max(3);
+        // age, size and the spaces between them
+        let created_by_width = cmd::get_terminal_display_width()
+            .saturating_sub(age_width + 12)
+            .max(20);
+
+        println!("{:<age_width$}  {:>9}  CREATED BY", "AGE", "SIZE");
+        for (layer, age) in layers.iter().zip(&ages) {
+            let mut created_by = dockerfile_helper_fns::normalize_created_by(&layer.created_by);
+            if !args.verbose && created_by.chars().count() > created_by_width {
+                created_by = format!(
+                    "{}...",
+                    created_by
+                        .chars()
+                        .take(created_by_width - 3)
+                        .collect::<String>()
+                );
+            }
+            println!(
+                "{:<age_width$}  {:>9}  {}",
+                age,
+                cmd::format_bytes(layer.size),
+                created_by
+            );
+        }
+        let total: u64 = layers.iter().map(|layer| layer.size).sum();
+        println!(
+            "{} layer(s), {} in all.",
+            layers.len(),
+            cmd::format_bytes(total)
+        );
+    }
+
+    fn diff_dockerfile_against_history(&mut self, entry: &DirEntry, image: &str) {
+        let dir = entry.path().parent().unwrap();
+        let dockerfile = match self.buildfile(dir) {
+            Some(dockerfile) => dockerfile,
+            None => {
+                eprintln!(
+                    "No Dockerfile or Containerfile found in '{}'",
+                    dir.display()
+                );
+                return;
+            }
+        };
+        let instructions = match dockerfile_helper_fns::read_instructions(&dockerfile) {
+            Ok(instructions) => instructions,
+            Err(e) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let history = match podman_helper_fns::get_podman_image_history(image) {
+            Ok(history) => history,
+            Err(e) => {
+                eprintln!("Error reading history of {}: {}", image, e);
+                return;
+            }
+        };
+
+        let rows = dockerfile_helper_fns::diff_against_history(&instructions, &history);
+        // two columns plus the marker and separator
+        let col_width = (cmd::get_terminal_display_width().saturating_sub(7) / 2).max(20);
+        let fit = |s: &str| {
+            if s.chars().count() > col_width {
+                let truncated: String = s.chars().take(col_width - 3).collect();
+                format!("{}...", truncated)
+            } else {
+                s.to_string()
+            }
+        };
+
+        println!(
+            "  {:<width$} | Image history",
+            "Dockerfile",
+            width = col_width
+        );
+        let mut changed = 0;
+        for row in &rows {
+            let left = format!(
+                "{:>3}: {} {}",
+                row.instruction.line, row.instruction.keyword, row.instruction.args
+            );
+            let marker = if row.history.is_some() { " " } else { "*" };
+            if row.history.is_none() {
+                changed += 1;
+            }
+            println!(
+                "{} {:<width$} | {}",
+                marker,
+                fit(&left),
+                fit(row.history.as_deref().unwrap_or("")),
+                width = col_width
+            );
+        }
+        if changed > 0 {
+            println!(
+                "{} instruction(s) marked * aren't in the image's history, a rebuild looks needed.",
+                changed
+            );
         } else {
-            format!("{} seconds ago", seconds)
+            println!("All instructions match the image's history.");
         }
     }
 
+    /// Whether `image` was pulled or built more recently than --since, so it isn't offered.
+    fn is_fresh(&mut self, image: &str, args: &Args) -> bool {
+        let since = match args.since {
+            Some(since) => since,
+            None => return false,
+        };
+        // an image that isn't on disk yet is never fresh
+        let modified = match podman_helper_fns::get_podman_ondisk_modify_time(image) {
+            Ok(modified) => modified,
+            Err(_) => return false,
+        };
+        let age = Local::now().signed_duration_since(modified);
+        if age.to_std().map(|age| age < since).unwrap_or(true) {
+            println!(
+                "Skipping {}, pulled {} (newer than --since)",
+                image,
+                self.format_time(modified)
+            );
+            return true;
+        }
+        false
+    }
+
+    /// What else uses `image`: containers created from it, and other compose and quadlet files naming it.
+    fn print_usage(&mut self, compose_file: &Path, image: &str, args: &Args) {
+        match podman_helper_fns::containers_using(image) {
+            Ok(containers) if containers.is_empty() => println!("Containers: none"),
+            Ok(containers) => println!("Containers: {}", containers.join(", ")),
+            Err(e) => eprintln!("Error listing containers of {}: {}", image, e),
+        }
+        if args.low_memory {
+            return;
+        }
+        let users = self
+            .image_users
+            .get_or_insert_with(|| repocheck::image_users(args));
+        let others: Vec<String> = users
+            .get(image)
+            .map(|files| {
+                files
+                    .iter()
+                    .filter(|f| f.as_path() != compose_file)
+                    .map(|f| f.display().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if !others.is_empty() {
+            println!("Also used in: {}", others.join(", "));
+        }
+    }
+
+    /// The usage report for just this image's repository, to spot other tags of it in use.
+    fn show_usage(&mut self, image: &str, args: &Args) {
+        let users = self
+            .image_users
+            .get_or_insert_with(|| repocheck::image_users(args));
+        let image = image_name::qualified_name(image);
+        let repository = image_name::repository_of(&image);
+        let usages: Vec<usage::RepositoryUsage> = usage::report(users)
+            .into_iter()
+            .filter(|u| u.repository == repository)
+            .collect();
+        usage::print(&usages, false);
+    }
+
+    fn print_devices(&self, service_config: &Value) {
+        let devices = compose::devices(service_config);
+        if !devices.is_empty() {
+            println!("Devices: {}", devices.join(", "));
+        }
+        for device in &devices {
+            // CDI names (vendor.com/class=name) aren't paths
+            if !device.contains('=') && !std::path::Path::new(device).exists() {
+                println!(
+                    "{} device {} doesn't exist on this host",
+                    color::paint("Warning:", color::YELLOW, color::stdout()),
+                    device
+                );
+            }
+        }
+        let gpus = compose::gpu_requests(service_config);
+        if !gpus.is_empty() {
+            println!("GPUs: {}", gpus.join("; "));
+        }
+    }
+
+    fn print_mounts(&self, compose_file: &Path, service_config: &Value) {
+        let mounts = compose::mounts(compose_file, service_config);
+        if mounts.is_empty() {
+            return;
+        }
+        println!("Volumes:");
+        for mount in &mounts {
+            println!("  {}", mount);
+        }
+        for source in mounts.iter().filter_map(|m| m.missing_bind_source()) {
+            println!(
+                "{} bind source {} doesn't exist on this host",
+                color::paint("Warning:", color::YELLOW, color::stdout()),
+                source.display()
+            );
+        }
+    }
+
+    /// Like `format_time`, in red on a terminal when `dt` is more than STALE_AFTER_DAYS ago.
+    fn format_age(&mut self, dt: DateTime<Local>) -> String {
+        let formatted = self.format_time(dt);
+        let stale = Local::now().signed_duration_since(dt).num_days() > STALE_AFTER_DAYS;
+        color::paint(&formatted, color::RED, stale && color::stdout())
+    }
+
+    fn format_time(&mut self, dt: DateTime<Local>) -> String {
+        format_time(dt, self.time_format)
+    }
+
     fn build_image_from_dockerfile(
         &mut self,
         dir: &DirEntry,
         image_name: &str,
         build_args: Vec<&str>,
-    ) {
-        let mut dockerfile = dir.path().to_path_buf().parent().unwrap().to_path_buf();
-        dockerfile.push("Dockerfile");
+        args: &Args,
+        no_cache: bool,
+        timeout: Option<Duration>,
+    ) -> Result<(), String> {
+        let compose_dir = dir.path().parent().unwrap();
+        let dockerfile = match self.buildfile(compose_dir) {
+            Some(dockerfile) => dockerfile,
+            None => {
+                let reason = format!(
+                    "No Dockerfile or Containerfile found in '{}'",
+                    compose_dir.display()
+                );
+                eprintln!("{}", reason);
+                return Err(reason);
+            }
+        };
 
         if !dockerfile.is_file()
             || !fs::metadata(&dockerfile).is_ok()
             || !fs::File::open(&dockerfile).is_ok()
         {
-            eprintln!("No Dockerfile found at '{}'", dockerfile.display());
-            std::process::exit(1);
+            let reason = format!("No readable build file at '{}'", dockerfile.display());
+            eprintln!("{}", reason);
+            return Err(reason);
         }
 
         let _ = cmd::pull_base_image(&dockerfile);
 
         let z = dockerfile.display().to_string();
 
-        let mut x = vec![];
-        x.push("build");
-        x.push("-t");
-        x.push(image_name);
-        x.push("-f");
-        x.push(&z);
+        let cache_bust = args
+            .build_arg_cache_bust
+            .as_ref()
+            .map(|name| format!("{}={}", name, Local::now().timestamp()));
+        let mut build_args: Vec<&str> = build_args;
+        if let Some(cache_bust) = &cache_bust {
+            build_args.push(cache_bust);
+        }
+
+        let labels = if args.no_provenance_labels {
+            vec![]
+        } else {
+            git_helper_fns::provenance_labels(compose_dir)
+        };
+        let labels: Vec<&str> = labels.iter().map(|l| l.as_str()).collect();
 
-        // let mut abc = string::String::new();
-        for arg in build_args {
-            x.push("--build-arg");
-            x.push(&arg);
+        let cache_ref = buildcache::cache_ref(args, compose_dir, image_name);
+        if args.platforms.is_empty() {
+            let x = engine_helper_fns::build_cmd_args(
+                image_name,
+                &z,
+                &build_args,
+                &labels,
+                cache_ref.as_deref(),
+                no_cache,
+                None,
+            );
+            return run_build(image_name, x, timeout);
+        }
+        // each arch is built, recorded and audited on its own; the manifest list only once all are built
+        let mut arch_images = vec![];
+        let mut failed = vec![];
+        for platform in &args.platforms {
+            let arch_image = engine_helper_fns::platform_image(image_name, platform);
+            println!("Building {} for {}", image_name, platform);
+            let started = Instant::now();
+            log_sink::set_context(&arch_image, "build");
+            let x = engine_helper_fns::build_cmd_args(
+                &arch_image,
+                &z,
+                &build_args,
+                &labels,
+                cache_ref.as_deref(),
+                no_cache,
+                Some(platform),
+            );
+            let result = run_build(&arch_image, x, timeout);
+            self.record_sub_result(&arch_image, "build", result.is_ok(), started);
+            audit::record(args, "build", &arch_image, build_outcome(&result));
+            match result {
+                Ok(()) => arch_images.push(arch_image),
+                Err(reason) => failed.push(format!("{} {}", platform, reason)),
+            }
         }
+        log_sink::set_context(image_name, "build");
+        if !failed.is_empty() {
+            return Err(failed.join(", "));
+        }
+        engine_helper_fns::assemble_manifest(image_name, &arch_images)?;
+        if args.push_manifest {
+            engine_helper_fns::push_manifest(image_name)?;
+        }
+        Ok(())
+    }
 
-        cmd::exec_cmd("podman", x);
+    fn prune_images(&mut self, args: &Args) {
+        match podman_helper_fns::prune_dangling_images() {
+            Ok((removed, reclaimed)) => {
+                self.bytes_reclaimed += reclaimed;
+                let msg = format!(
+                    "Pruned {} dangling image(s), reclaimed {}.",
+                    removed,
+                    cmd::format_bytes(reclaimed)
+                );
+                audit::record(args, "prune", "dangling images", &msg);
+                println!("{}", msg);
+            }
+            Err(e) => {
+                audit::record(args, "prune", "dangling images", &e);
+                eprintln!("Error pruning dangling images: {}", e);
+            }
+        }
     }
 
     // other methods...
 
-    fn read_yaml_file(&mut self, file: &str) -> Value {
-        let file = File::open(file).expect("file not found");
-        let yaml: Value = serde_yaml::from_reader(file).expect("Error reading file");
-        yaml
-    }
-
-    fn pull_it(&mut self, image: &str) {
+    fn pull_it(&mut self, image: &str) -> bool {
         let mut x = vec![];
 
         x.push("pull");
         x.push(image);
-        cmd::exec_cmd("podman", x);
+        cmd::exec_cmd(engine_helper_fns::engine().binary(), x).success()
+    }
+}
+
+/// Run a build command, stopping it after `timeout`.
+fn run_build(image_name: &str, x: Vec<&str>, timeout: Option<Duration>) -> Result<(), String> {
+    let (status, timed_out) =
+        cmd::exec_cmd_with_timeout(engine_helper_fns::engine().binary(), x, timeout);
+    if timed_out {
+        let reason = format!("timed out after {}s", timeout.unwrap_or_default().as_secs());
+        eprintln!("Stopped the build of {}, it {}.", image_name, reason);
+        Err(reason)
+    } else if status.success() {
+        Ok(())
+    } else {
+        Err("failed".to_string())
+    }
+}
+
+/// The audit log outcome of a build.
+fn build_outcome(result: &Result<(), String>) -> &str {
+    match result {
+        Ok(()) => "ok",
+        Err(reason) => reason,
+    }
+}
+
+/// `dt` as --time-format says.
+pub fn format_time(dt: DateTime<Local>, time_format: TimeFormat) -> String {
+    let absolute = || dt.to_rfc3339_opts(SecondsFormat::Secs, false);
+    match time_format {
+        TimeFormat::Relative => format_time_ago(dt),
+        TimeFormat::Absolute => absolute(),
+        TimeFormat::Both => format!("{} ({})", format_time_ago(dt), absolute()),
+    }
+}
+
+fn format_time_ago(dt: DateTime<Local>) -> String {
+    let now = Local::now();
+    let duration = now.signed_duration_since(dt);
+    let days = duration.num_days();
+    let hours = duration.num_hours();
+    let minutes = duration.num_minutes();
+    let seconds = duration.num_seconds();
+    if days > 0 {
+        format!("{} days ago", days)
+    } else if hours > 0 {
+        format!("{} hours ago", hours)
+    } else if minutes > 0 {
+        format!("{} minutes ago", minutes)
+    } else {
+        format!("{} seconds ago", seconds)
     }
 }
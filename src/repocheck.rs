@@ -0,0 +1,307 @@
+use crate::args::Args;
+use crate::compose;
+use crate::dockerfilelint;
+use crate::helpers::dockerfile_helper_fns;
+use crate::helpers::ignore_helper_fns::IgnoreRules;
+use crate::imagename;
+use crate::lint::{Finding, Severity};
+
+use regex::Regex;
+use serde_yaml::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Structural problems across the whole tree, rather than within one compose file:
+/// services with no image, build files with no compose file to name their image, `build:` contexts
+/// without a build file, bind mounts whose host path doesn't exist, quadlet `.container` files
+/// using a local image nothing builds or a `.volume`/`.network` unit that isn't in the tree, and
+/// container names used by more than one compose file.
+/// With --lint, also whatever `dockerfilelint` finds in each build file.
+pub fn check_repository(args: &Args) -> Vec<Finding> {
+    let exclude: Vec<Regex> = args
+        .exclude_path_patterns
+        .iter()
+        .filter_map(|p| Regex::new(p).ok())
+        .collect();
+    let include: Vec<Regex> = args
+        .include_path_patterns
+        .iter()
+        .filter_map(|p| Regex::new(p).ok())
+        .collect();
+    let selected = |path: &Path| {
+        let path = path.to_string_lossy();
+        !exclude.iter().any(|p| p.is_match(&path)) && include.iter().all(|p| p.is_match(&path))
+    };
+
+    let mut findings = vec![];
+    // from --lint, added after the rest since `finding` holds `findings` until then
+    let mut buildfile_findings = vec![];
+    let mut finding = |file: &Path, severity: Severity, message: String| {
+        findings.push(Finding {
+            file: file.to_path_buf(),
+            line: 0,
+            severity,
+            message,
+        })
+    };
+    // container name -> compose files using it
+    let mut container_names: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    let mut buildfile_dirs: HashSet<PathBuf> = HashSet::new();
+    // quadlet `.volume`/`.network` file names, and the `.container` files referring to each
+    let mut quadlet_units: HashSet<String> = HashSet::new();
+    let mut quadlet_refs: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+
+    let files = scanned_files(args)
+        .into_iter()
+        .filter(|file| selected(file))
+        .collect::<Vec<PathBuf>>();
+
+    for file in &files {
+        let dir = file.parent().unwrap_or(Path::new("."));
+        let name = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if compose::is_primary_compose_file(file) {
+            let yaml = match compose::read_compose(file) {
+                Ok(yaml) => yaml,
+                Err(e) => {
+                    finding(file, Severity::Error, e);
+                    continue;
+                }
+            };
+            for (service, config) in compose::services(&yaml) {
+                if config.get("image").is_none() && config.get("build").is_none() {
+                    finding(
+                        file,
+                        Severity::Error,
+                        format!("service `{}` has no image", service),
+                    );
+                }
+                if let Some(missing) = missing_build_file(dir, config) {
+                    finding(
+                        file,
+                        Severity::Error,
+                        format!(
+                            "service `{}` builds from {}, which doesn't exist",
+                            service,
+                            missing.display()
+                        ),
+                    );
+                }
+                for mount in compose::mounts(file, config) {
+                    if let Some(source) = mount.missing_bind_source() {
+                        finding(
+                            file,
+                            Severity::Warning,
+                            format!(
+                                "service `{}` bind-mounts {}, which doesn't exist",
+                                service,
+                                source.display()
+                            ),
+                        );
+                    }
+                }
+                if let Some(container_name) = config.get("container_name").and_then(|c| c.as_str())
+                {
+                    let vars = compose::interpolation_vars(file, config);
+                    let container_name = compose::interpolate(container_name, &vars).0;
+                    container_names
+                        .entry(container_name)
+                        .or_default()
+                        .push(file.clone());
+                }
+            }
+        } else if name.ends_with(".volume") || name.ends_with(".network") {
+            quadlet_units.insert(name);
+        } else if name.ends_with(".container") {
+            for unit in quadlet_unit_refs(file) {
+                quadlet_refs.entry(unit).or_default().push(file.clone());
+            }
+            if let Some(image) = quadlet_image(file) {
+                if image.starts_with("localhost/")
+                    && dockerfile_helper_fns::find_buildfile(dir).is_none()
+                {
+                    finding(
+                        file,
+                        Severity::Error,
+                        format!(
+                            "uses local image {} but there's no Dockerfile or Containerfile next to it",
+                            image
+                        ),
+                    );
+                }
+            }
+        } else if dockerfile_helper_fns::is_buildfile_name(&name) {
+            buildfile_dirs.insert(dir.to_path_buf());
+            if args.lint {
+                buildfile_findings.extend(dockerfilelint::lint_buildfile(file));
+            }
+        }
+    }
+
+    for dir in &buildfile_dirs {
+        if nothing_names_image(dir) {
+            let buildfile = dockerfile_helper_fns::find_buildfile(dir).unwrap();
+            let message = match &args.image_name_template {
+                Some(template) => format!(
+                    "no compose or .container file next to it, --image-name-template names it {}",
+                    imagename::from_template(template, &buildfile)
+                ),
+                None => {
+                    "no compose or .container file next to it, so no image name can be inferred"
+                        .to_string()
+                }
+            };
+            finding(&buildfile, Severity::Warning, message);
+        }
+    }
+
+    for (unit, files) in &quadlet_refs {
+        if !quadlet_units.contains(unit) {
+            for file in files {
+                finding(
+                    file,
+                    Severity::Warning,
+                    format!("refers to {}, which isn't in the tree", unit),
+                );
+            }
+        }
+    }
+
+    for (container_name, files) in &container_names {
+        let unique: HashSet<&PathBuf> = files.iter().collect();
+        if unique.len() > 1 {
+            for file in files {
+                finding(
+                    file,
+                    Severity::Error,
+                    format!(
+                        "container name `{}` is used in {} compose files",
+                        container_name,
+                        unique.len()
+                    ),
+                );
+            }
+        }
+    }
+    findings.extend(buildfile_findings);
+    findings
+}
+
+/// Whether `dir` has no compose or quadlet `.container` file to name the image its build file makes.
+pub fn nothing_names_image(dir: &Path) -> bool {
+    let has_compose = compose::COMPOSE_FILE_NAMES
+        .iter()
+        .any(|n| dir.join(n).is_file());
+    let has_quadlet = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .any(|e| e.file_name().to_string_lossy().ends_with(".container"))
+        })
+        .unwrap_or(false);
+    !has_compose && !has_quadlet
+}
+
+/// The build file a service's `build:` points at, when it doesn't exist.
+fn missing_build_file(compose_dir: &Path, service_config: &Value) -> Option<PathBuf> {
+    let (context, dockerfile) = match service_config.get("build")? {
+        Value::String(context) => (context.as_str(), None),
+        build => (
+            build.get("context").and_then(|c| c.as_str()).unwrap_or("."),
+            build.get("dockerfile").and_then(|d| d.as_str()),
+        ),
+    };
+    let context = compose_dir.join(context.trim_start_matches("./"));
+    match dockerfile {
+        Some(dockerfile) => {
+            let path = context.join(dockerfile);
+            (!path.is_file()).then_some(path)
+        }
+        None => match dockerfile_helper_fns::find_buildfile(&context) {
+            Some(_) => None,
+            None => Some(context.join("Dockerfile")),
+        },
+    }
+}
+
+/// The files under the scan roots, skipping VCS dirs and what .gitignore files ignore like the scan
+/// does, unless --no-ignore.
+fn scanned_files(args: &Args) -> Vec<PathBuf> {
+    let ignore_rules = IgnoreRules::new();
+    args.scan_roots()
+        .iter()
+        .flat_map(|root| {
+            WalkDir::new(root)
+                .into_iter()
+                .filter_entry(|e| {
+                    e.depth() == 0
+                        || args.no_ignore
+                        || !ignore_rules.is_ignored(root, e.path(), e.file_type().is_dir())
+                })
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .map(|e| e.into_path())
+                .collect::<Vec<PathBuf>>()
+        })
+        .collect()
+}
+
+/// Every compose and quadlet `.container` file under the scan roots, by the images they use.
+pub fn image_users(args: &Args) -> HashMap<String, Vec<PathBuf>> {
+    let mut users: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for file in scanned_files(args) {
+        if compose::is_primary_compose_file(&file) {
+            if let Ok(yaml) = compose::read_compose(&file) {
+                for (_, config) in compose::services(&yaml) {
+                    if let Some((image, _)) = compose::resolved_image(&file, config) {
+                        users.entry(image).or_default().push(file.clone());
+                    }
+                }
+            }
+        } else if file.extension().is_some_and(|e| e == "container") {
+            if let Some(image) = quadlet_image(&file) {
+                users.entry(image).or_default().push(file.clone());
+            }
+        }
+    }
+    for files in users.values_mut() {
+        files.dedup();
+    }
+    users
+}
+
+/// `Image=` from the `[Container]` section of a quadlet file.
+fn quadlet_image(file: &Path) -> Option<String> {
+    let content = fs::read_to_string(file).ok()?;
+    content
+        .lines()
+        .map(|l| l.trim())
+        .find_map(|l| l.strip_prefix("Image="))
+        .map(|image| image.trim().to_string())
+}
+
+/// The `.volume` and `.network` units a quadlet `.container` uses, from `Volume=name.volume:/path`
+/// and `Network=name.network`. Named podman volumes and networks without a unit are left alone.
+fn quadlet_unit_refs(file: &Path) -> Vec<String> {
+    let content = match fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(_) => return vec![],
+    };
+    content
+        .lines()
+        .map(|l| l.trim())
+        .filter_map(|l| {
+            let (value, suffix) = match l.strip_prefix("Volume=") {
+                Some(value) => (value, ".volume"),
+                None => (l.strip_prefix("Network=")?, ".network"),
+            };
+            let source = value.trim().split(':').next().unwrap_or("");
+            source.ends_with(suffix).then(|| source.to_string())
+        })
+        .collect()
+}
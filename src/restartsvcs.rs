@@ -1,5 +1,17 @@
 use crate::args::Args;
+use crate::compose;
+use crate::depgraph::DependencyGraph;
+use crate::helpers::audit_helper_fns as audit;
 use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::podman_helper_fns;
+
+use serde_yaml::Value;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
 pub fn restart_services(args: &Args) {
     if args.verbose {
@@ -12,3 +24,133 @@ pub fn restart_services(args: &Args) {
 
     cmd::exec_cmd("podman", x);
 }
+
+/// What in a service needs root that rootless podman won't be able to do. Empty when podman runs rootful.
+pub fn rootless_problems(service_config: &Value) -> Vec<String> {
+    if !podman_helper_fns::is_rootless().unwrap_or(false) {
+        return vec![];
+    }
+    let mut problems = vec![];
+    if compose::is_privileged(service_config) {
+        problems.push("privileged: true".to_string());
+    }
+    let port_start = podman_helper_fns::unprivileged_port_start();
+    for port in compose::published_ports(service_config) {
+        if port < port_start {
+            problems.push(format!("host port {} is below {}", port, port_start));
+        }
+    }
+    problems
+}
+
+/// After `image` was rebuilt or pulled, recreate the services in `compose_file` that use it,
+/// then restart everything depending on them, dependencies first. Recreated services with a
+/// healthcheck are waited on; returns how many of those didn't turn healthy.
+pub fn restart_after_build(compose_file: &Path, image: &str, args: &Args) -> usize {
+    let yaml = match compose::read_compose(compose_file) {
+        Ok(yaml) => yaml,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return 0;
+        }
+    };
+    let mut unhealthy = 0;
+    let graph = DependencyGraph::from_yaml(compose_file, &yaml);
+    let rebuilt: Vec<String> = graph
+        .services
+        .iter()
+        .filter(|s| s.image.as_deref() == Some(image))
+        .map(|s| s.name.clone())
+        .collect();
+    let to_restart = graph.with_dependents(&rebuilt);
+    let file_args = compose::file_args(compose_file);
+
+    for service in graph
+        .dependency_order()
+        .into_iter()
+        .filter(|s| to_restart.contains(&s.name))
+    {
+        let mut x: Vec<&str> = file_args.iter().map(|s| s.as_str()).collect();
+        let (action, audit_action) = if rebuilt.contains(&service.name) {
+            // a plain restart would keep running the old image
+            x.extend(["up", "-d", "--no-deps", "--force-recreate"]);
+            ("Recreated", "recreate")
+        } else {
+            x.push("restart");
+            ("Restarted", "restart")
+        };
+        x.push(service.name.as_str());
+        if args.verbose {
+            println!("podman-compose {}", x.join(" "));
+        }
+
+        let label = match &service.container_name {
+            Some(container) => format!("{} ({})", service.name, container),
+            None => service.name.clone(),
+        };
+        let service_config = compose::services(&yaml)
+            .into_iter()
+            .find(|(name, _)| name == &service.name)
+            .map(|(_, config)| config);
+        if let Some(service_config) = service_config {
+            for problem in rootless_problems(service_config) {
+                color::warn(format!(
+                    "{} needs root but podman is rootless: {}",
+                    label, problem
+                ));
+            }
+        }
+        let success = cmd::exec_cmd("podman-compose", x).success();
+        // podman-compose names containers itself when there's no container_name, so those aren't waited on
+        let health = match (&service.container_name, service_config) {
+            (Some(container), Some(config))
+                if success
+                    && rebuilt.contains(&service.name)
+                    && compose::has_healthcheck(config) =>
+            {
+                Some(wait_until_healthy(container, args.health_timeout))
+            }
+            _ => None,
+        };
+        let outcome = match &health {
+            Some(Ok(())) => "ok, healthy".to_string(),
+            Some(Err(e)) => {
+                unhealthy += 1;
+                format!("ok, UNHEALTHY: {}", e)
+            }
+            None if success => "ok".to_string(),
+            None => "FAILED".to_string(),
+        };
+        audit::record(
+            args,
+            audit_action,
+            &label,
+            match &health {
+                Some(Err(_)) => "unhealthy",
+                _ => audit::outcome(success),
+            },
+        );
+        println!("{} {}: {}", action, label, outcome);
+    }
+    unhealthy
+}
+
+/// Poll the container's health until it's healthy, unhealthy or `timeout` passes.
+fn wait_until_healthy(container: &str, timeout: Duration) -> Result<(), String> {
+    let started = Instant::now();
+    loop {
+        match podman_helper_fns::health_status(container)?.as_str() {
+            "healthy" => return Ok(()),
+            "unhealthy" => return Err("healthcheck failing".to_string()),
+            // starting
+            _ => {}
+        }
+        if started.elapsed() >= timeout {
+            return Err(format!(
+                "not healthy after {}s (--health-timeout)",
+                timeout.as_secs()
+            ));
+        }
+        thread::sleep(HEALTH_POLL_INTERVAL);
+    }
+}
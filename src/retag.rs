@@ -0,0 +1,177 @@
+use crate::args::{Args, BuildEngine};
+use crate::helpers::audit_helper_fns as audit;
+use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::engine_helper_fns;
+use crate::helpers::ignore_helper_fns;
+use crate::helpers::podman_helper_fns;
+use crate::read_val;
+
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::io::{self, IsTerminal};
+
+/// How to turn one image name into another.
+pub enum Edit {
+    /// `old=new`: replace every `old` in the name with `new`
+    Replace(String, String),
+    /// `s/regex/replacement/`, with `$1`-style groups in the replacement
+    Regex(Regex, String),
+}
+
+impl Edit {
+    pub fn parse(spec: &str) -> Result<Edit, String> {
+        if let Some(body) = spec.strip_prefix("s/") {
+            let body = body.strip_suffix('/').unwrap_or(body);
+            let (find, replacement) = body
+                .split_once('/')
+                .ok_or_else(|| format!("'{}' isn't s/regex/replacement/", spec))?;
+            let regex = Regex::new(find).map_err(|e| format!("Invalid regex '{}': {}", find, e))?;
+            return Ok(Edit::Regex(regex, replacement.to_string()));
+        }
+        match spec.split_once('=') {
+            Some((old, new)) if !old.is_empty() => {
+                Ok(Edit::Replace(old.to_string(), new.to_string()))
+            }
+            _ => Err(format!(
+                "'{}' is neither old=new nor s/regex/replacement/",
+                spec
+            )),
+        }
+    }
+
+    pub fn apply(&self, name: &str) -> String {
+        match self {
+            Edit::Replace(old, new) => name.replace(old.as_str(), new),
+            Edit::Regex(regex, replacement) => {
+                regex.replace_all(name, replacement.as_str()).to_string()
+            }
+        }
+    }
+}
+
+/// `1-3 7` or `all` -> indexes into a list of `len`, counting from 1 as the list is shown.
+pub fn parse_selection(input: &str, len: usize) -> Result<BTreeSet<usize>, String> {
+    let input = input.trim();
+    if input.is_empty() || input == "all" {
+        return Ok((0..len).collect());
+    }
+    let mut selected = BTreeSet::new();
+    for part in input.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+        let (from, to) = part.split_once('-').unwrap_or((part, part));
+        let number = |s: &str| match s.parse::<usize>() {
+            Ok(n) if (1..=len).contains(&n) => Ok(n),
+            _ => Err(format!("'{}' isn't a number from 1 to {}", s, len)),
+        };
+        let (from, to) = (number(from)?, number(to)?);
+        selected.extend((from.min(to)..=from.max(to)).map(|n| n - 1));
+    }
+    Ok(selected)
+}
+
+/// Rename several local images at once: list them (those matching --images), pick some, give one
+/// edit for all of their names, check the old -> new list and confirm. Each is `podman tag`ged with
+/// its new name and its old one `podman untag`ged, unless --keep-old-names. Returns whether all went through.
+pub fn run(args: &Args) -> bool {
+    let mut images = match podman_helper_fns::local_image_names() {
+        Ok(images) => images,
+        Err(e) => {
+            eprintln!("Error listing images: {}", e);
+            return false;
+        }
+    };
+    if let Some(pattern) = &args.images {
+        let regex = Regex::new(&ignore_helper_fns::glob_to_regex(pattern)).unwrap();
+        images.retain(|image| regex.is_match(image));
+    }
+    if images.is_empty() {
+        println!("No images to rename.");
+        return true;
+    }
+    let interactive = io::stdin().is_terminal();
+
+    let selected: Vec<&String> = if interactive {
+        for (i, image) in images.iter().enumerate() {
+            println!("{:>3}  {}", i + 1, image);
+        }
+        loop {
+            print!("Images to rename, e.g. 1-3 7 (Enter for all): ");
+            let input = read_val::read_line().unwrap_or_default();
+            match parse_selection(&input, images.len()) {
+                Ok(selection) => break selection.into_iter().map(|i| &images[i]).collect(),
+                Err(e) => println!("{}", e),
+            }
+        }
+    } else {
+        images.iter().collect()
+    };
+
+    // --rename was checked in Args::validate, and is there when there's no terminal
+    let edit = match &args.rename {
+        Some(spec) => Edit::parse(spec).unwrap(),
+        None => loop {
+            print!("Edit for all of them, old=new or s/regex/replacement/ (empty to cancel): ");
+            let input = read_val::read_line().unwrap_or_default();
+            if input.trim().is_empty() {
+                return true;
+            }
+            match Edit::parse(input.trim()) {
+                Ok(edit) => break edit,
+                Err(e) => println!("{}", e),
+            }
+        },
+    };
+
+    let renames: Vec<(&String, String)> = selected
+        .into_iter()
+        .map(|image| (image, edit.apply(image)))
+        .filter(|(image, new_name)| *image != new_name && !new_name.is_empty())
+        .collect();
+    if renames.is_empty() {
+        println!("The edit doesn't change any of the names.");
+        return true;
+    }
+    let width = renames.iter().map(|(image, _)| image.len()).max().unwrap();
+    for (image, new_name) in &renames {
+        println!("{:<width$}  ->  {}", image, new_name, width = width);
+    }
+    if !args.yes {
+        print!("Rename these {} image(s)? y/N: ", renames.len());
+        match read_val::read_line() {
+            Ok(answer) if answer.trim().eq_ignore_ascii_case("y") => {}
+            _ => {
+                println!("Left the images as they were.");
+                return true;
+            }
+        }
+    }
+
+    let binary = engine_helper_fns::engine().store_binary();
+    let mut all_ok = true;
+    for (image, new_name) in &renames {
+        let mut renamed = cmd::exec_cmd(binary, vec!["tag", image, new_name]).success();
+        if renamed && !args.keep_old_names {
+            // docker has no untag, but rmi of a name the image still has another of only drops the name
+            let untag = match engine_helper_fns::engine() {
+                BuildEngine::Docker => vec!["rmi", image.as_str()],
+                _ => vec!["untag", image.as_str(), image.as_str()],
+            };
+            renamed = cmd::exec_cmd(binary, untag).success();
+        }
+        audit::record(
+            args,
+            "retag",
+            image,
+            &format!("{} as {}", audit::outcome(renamed), new_name),
+        );
+        if renamed {
+            println!("Renamed {} to {}", image, new_name);
+        } else {
+            eprintln!("Error renaming {} to {}", image, new_name);
+            all_ok = false;
+        }
+    }
+    all_ok
+}
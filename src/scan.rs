@@ -0,0 +1,98 @@
+use crate::args::Args;
+use crate::helpers::audit_helper_fns as audit;
+
+use serde_json::Value;
+use std::process::Command;
+
+const SEVERITIES: [&str; 5] = ["CRITICAL", "HIGH", "MEDIUM", "LOW", "UNKNOWN"];
+
+struct Finding {
+    id: String,
+    severity: String,
+    package: String,
+    installed: String,
+    fixed: String,
+}
+
+/// Scan `image` from podman's local storage with trivy and print how many findings there are per severity,
+/// or every finding with --verbose.
+pub fn scan_image(image: &str, args: &Args) {
+    match run_trivy(image) {
+        Ok(findings) => {
+            let counts: Vec<String> = SEVERITIES
+                .iter()
+                .map(|sev| {
+                    let n = findings.iter().filter(|f| f.severity == *sev).count();
+                    format!("{} {}", sev, n)
+                })
+                .collect();
+            let summary = format!("Vulnerabilities: {}", counts.join(", "));
+            println!("{}", summary);
+            if args.verbose {
+                for f in &findings {
+                    println!(
+                        "  {:<8} {:<20} {} {} (fixed in: {})",
+                        f.severity,
+                        f.id,
+                        f.package,
+                        f.installed,
+                        if f.fixed.is_empty() { "-" } else { &f.fixed }
+                    );
+                }
+            }
+            audit::record(args, "scan", image, &summary);
+        }
+        Err(e) => {
+            audit::record(args, "scan", image, &e);
+            eprintln!("Error scanning {}: {}", image, e);
+        }
+    }
+}
+
+fn run_trivy(image: &str) -> Result<Vec<Finding>, String> {
+    let output = Command::new("trivy")
+        .args([
+            "image",
+            "--quiet",
+            "--format",
+            "json",
+            "--image-src",
+            "podman",
+            image,
+        ])
+        .output()
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => "trivy not found on PATH".to_string(),
+            _ => format!("Failed to execute trivy: {}", e),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "trivy failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    let report: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse trivy output: {}", e))?;
+
+    let field = |v: &Value, key: &str| v[key].as_str().unwrap_or("").to_string();
+    let mut findings = vec![];
+    for result in report["Results"].as_array().into_iter().flatten() {
+        for vuln in result["Vulnerabilities"].as_array().into_iter().flatten() {
+            findings.push(Finding {
+                id: field(vuln, "VulnerabilityID"),
+                severity: field(vuln, "Severity"),
+                package: field(vuln, "PkgName"),
+                installed: field(vuln, "InstalledVersion"),
+                fixed: field(vuln, "FixedVersion"),
+            });
+        }
+    }
+    // most severe first
+    findings.sort_by_key(|f| {
+        SEVERITIES
+            .iter()
+            .position(|s| *s == f.severity)
+            .unwrap_or(SEVERITIES.len())
+    });
+    Ok(findings)
+}
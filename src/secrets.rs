@@ -1,4 +1,11 @@
 use crate::args::Args;
+use crate::helpers::audit_helper_fns as audit;
+use crate::helpers::cmd_helper_fns as cmd;
+use crate::helpers::color_helper_fns as color;
+use crate::helpers::encryption_helper_fns as encryption;
+use crate::helpers::host_helper_fns as host;
+use crate::helpers::ignore_helper_fns as ignore;
+use crate::helpers::signing_helper_fns as signing;
 use crate::read_val::{self, Grammar, GrammerType};
 
 use chrono::{DateTime, Local, TimeZone, Utc};
@@ -15,17 +22,24 @@ use std::{fs, path};
 // use std::io::{BufRead, BufReader};
 use std::io::{Read, Write};
 // use std::path::PathBuf;
+use azure_core::auth::TokenCredential;
 use azure_identity::ClientSecretCredential;
 use azure_security_keyvault::{KeyvaultClient, SecretClient};
-use hostname;
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::runtime::Runtime;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use walkdir::WalkDir;
 // use chrono::{DateTime, FixedOffset};
 use time::OffsetDateTime;
 // use url::Url;
 
+static MISMATCHES: AtomicUsize = AtomicUsize::new(0);
+
 struct SetSecretResponse {
     created: OffsetDateTime,
     updated: OffsetDateTime,
@@ -44,6 +58,16 @@ struct JsonOutput {
     az_updated: String,
     az_name: String,
     hostname: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    encryption: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signature: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host_overrides: Option<Value>,
 }
 
 struct JsonOutputControl {
@@ -63,12 +87,37 @@ impl JsonOutputControl {
                 az_updated: String::new(),
                 az_name: String::new(),
                 hostname: String::new(),
+                encryption: None,
+                nonce: None,
+                signature: None,
+                signer: None,
+                host_overrides: None,
             },
             validate_all: false,
         }
     }
 }
 
+/// With --secret-mode-input-json on upload, the entries whose file is at a host override here, by
+/// that local path. Uploading one keeps the entry's own `filenm`, and the secret name made from it,
+/// so every host uploads to the same secret.
+fn host_overridden_entries(args: &Args) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let Some(input_json) = &args.secret_mode_input_json else {
+        return Ok(HashMap::new());
+    };
+    let content = fs::read_to_string(input_json)
+        .map_err(|e| format!("Failed to read '{}': {}", input_json.display(), e))?;
+    let entries: Vec<Value> = serde_json::from_str(&content)
+        .map_err(|e| format!("Failed to parse '{}': {}", input_json.display(), e))?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|entry| {
+            let local = host::local_filenm(&entry)?;
+            (Some(local) != entry["filenm"].as_str()).then(|| (local.to_string(), entry.clone()))
+        })
+        .collect())
+}
+
 pub fn update_mode(args: &Args) -> Result<(), Box<dyn Error>> {
     let mut output_entries = vec![];
 
@@ -81,47 +130,105 @@ pub fn update_mode(args: &Args) -> Result<(), Box<dyn Error>> {
     let kev_vault_name = args.secrets_vault_name.as_ref().unwrap();
 
     let client = get_keyvault_secret_client(client_id, client_secret, tenant_id, kev_vault_name);
+    let deleted_secrets = DeletedSecrets::new(client_id, client_secret, tenant_id, kev_vault_name);
+    let cipher = match &args.encryption_key_file {
+        Some(key_file) => Some(encryption::read_key(key_file)?),
+        None => None,
+    };
+    let signing_key = match &args.signing_key_file {
+        Some(key_file) => {
+            let key = signing::read_signing_key(key_file)?;
+            println!(
+                "Signing with public key {}, pass a file holding it as --verify-key-file when retrieving.",
+                signing::public_key(&key)
+            );
+            Some(key)
+        }
+        None => None,
+    };
 
     let rt = Runtime::new().unwrap();
 
-    for entry in WalkDir::new(args.path.clone())
-        .into_iter()
-        .filter_map(Result::ok)
-    {
-        if entry.file_name() == ".env" && entry.file_type().is_file() {
-            let full_path = entry.path().to_string_lossy().to_string();
-            // strip out the platform-dependent path separator
-            let stripped_path = full_path.trim_start_matches(std::path::MAIN_SEPARATOR);
-
-            // Translate non-alphanumeric characters to '-'
-            let secret_name = re.replace_all(stripped_path, "-");
-
-            // Read file content
-            let content = fs::read_to_string(&full_path).unwrap();
-            let md5_checksum = calculate_md5(content.as_str());
-
-            // Insert secret into Azure Key Vault
-            let azure_response = rt
-                .block_on(set_secret_value(&secret_name, &client, &content))
-                .unwrap();
-
-            // Get current timestamp
-            let start = SystemTime::now();
-            let ins_ts = start.duration_since(UNIX_EPOCH).unwrap().as_secs();
-
-            // Build output entry
-            let output_entry = json!({
-                "filenm": full_path,
-                "md5": md5_checksum,
-                "ins_ts": ins_ts,
-                "az_id": azure_response.id,
-                "az_create": azure_response.created,
-                "az_updated": azure_response.updated,
-                "az_name": azure_response.name
-            });
+    let files = files_to_upload(args)?;
+    let overridden = host_overridden_entries(args)?;
+    if !args.secret_include.is_empty() && !args.yes && !confirm_upload(&files, kev_vault_name)? {
+        println!("Nothing uploaded.");
+        return Ok(());
+    }
+
+    for (full_path, content) in files {
+        let shared_entry = overridden.get(&full_path);
+        let filenm = shared_entry
+            .and_then(|entry| entry["filenm"].as_str())
+            .unwrap_or(&full_path);
+        // strip out the platform-dependent path separator
+        let stripped_path = filenm.trim_start_matches(std::path::MAIN_SEPARATOR);
 
-            output_entries.push(output_entry);
+        // Translate non-alphanumeric characters to '-'
+        let secret_name = re.replace_all(stripped_path, "-");
+
+        let md5_checksum = calculate_md5(content.as_str());
+        let (upload, nonce) = match &cipher {
+            Some(cipher) => {
+                let (ciphertext, nonce) = encryption::encrypt(cipher, &content)?;
+                (ciphertext, Some(nonce))
+            }
+            None => (content.clone(), None),
+        };
+        // what's signed is what the vault holds, ciphertext if encrypted
+        let signature = signing_key
+            .as_ref()
+            .map(|key| signing::sign(key, &secret_name, &upload));
+
+        // Insert secret into Azure Key Vault
+        let mut result = rt.block_on(set_secret_value(&secret_name, &client, &upload));
+        if let Err(e) = &result {
+            if is_soft_deleted_conflict(&e.to_string()) {
+                if !resolve_soft_deleted(&secret_name, &deleted_secrets, &rt)? {
+                    audit::record(args, "secret-upload", &full_path, "skipped");
+                    continue;
+                }
+                result = rt.block_on(set_secret_value_with_retry(&secret_name, &client, &upload));
+            }
         }
+        let azure_response = match result {
+            Ok(azure_response) => {
+                audit::record(args, "secret-upload", &full_path, "ok");
+                azure_response
+            }
+            Err(e) => {
+                audit::record(args, "secret-upload", &full_path, &e.to_string());
+                return Err(e);
+            }
+        };
+
+        // Get current timestamp
+        let start = SystemTime::now();
+        let ins_ts = start.duration_since(UNIX_EPOCH).unwrap().as_secs();
+
+        // Build output entry
+        let mut output_entry = json!({
+            "filenm": filenm,
+            "md5": md5_checksum,
+            "ins_ts": ins_ts,
+            "az_id": azure_response.id,
+            "az_create": azure_response.created,
+            "az_updated": azure_response.updated,
+            "az_name": azure_response.name
+        });
+        if let Some(nonce) = nonce {
+            output_entry["encryption"] = json!(encryption::ALGORITHM);
+            output_entry["nonce"] = json!(nonce);
+        }
+        if let (Some(signature), Some(key)) = (signature, &signing_key) {
+            output_entry["signature"] = json!(signature);
+            output_entry["signer"] = json!(signing::public_key(key));
+        }
+        if let Some(entry) = shared_entry {
+            output_entry["host_overrides"] = entry["host_overrides"].clone();
+        }
+
+        output_entries.push(output_entry);
     }
 
     // Append entries to output_file.txt
@@ -139,6 +246,71 @@ pub fn update_mode(args: &Args) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// The files secrets upload sends, with their content: those matching --secret-include, else
+/// every `.env` file under --path. Files that aren't UTF-8 text are left out with a warning, since
+/// a vault secret is a string.
+fn files_to_upload(args: &Args) -> Result<Vec<(String, String)>, Box<dyn Error>> {
+    let mut globs = vec![];
+    for glob in &args.secret_include {
+        globs.push(
+            Regex::new(&ignore::glob_to_regex(glob))
+                .map_err(|e| format!("Invalid --secret-include '{}': {}", glob, e))?,
+        );
+    }
+
+    let mut files = vec![];
+    for entry in WalkDir::new(args.path.clone())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|e| e.file_type().is_file())
+    {
+        let wanted = if globs.is_empty() {
+            entry.file_name() == ".env"
+        } else {
+            let relative = entry
+                .path()
+                .strip_prefix(&args.path)
+                .unwrap_or(entry.path());
+            let relative = relative.to_string_lossy();
+            globs.iter().any(|glob| glob.is_match(&relative))
+        };
+        if !wanted {
+            continue;
+        }
+        let full_path = entry.path().to_string_lossy().to_string();
+        let bytes =
+            fs::read(entry.path()).map_err(|e| format!("Failed to read '{}': {}", full_path, e))?;
+        match String::from_utf8(bytes) {
+            Ok(content) => files.push((full_path, content)),
+            Err(_) => color::warn(format!("skipping {}, it isn't UTF-8 text", full_path)),
+        }
+    }
+    Ok(files)
+}
+
+/// List what --secret-include picked, with size and md5, and ask before uploading.
+fn confirm_upload(files: &[(String, String)], vault_name: &str) -> Result<bool, Box<dyn Error>> {
+    if files.is_empty() {
+        return Ok(false);
+    }
+    for (full_path, content) in files {
+        println!(
+            "{:>9}  {}  {}",
+            cmd::format_bytes(content.len() as u64),
+            calculate_md5(content),
+            full_path
+        );
+    }
+    print!("Upload {} file(s) to {}? y/N: ", files.len(), vault_name);
+    let answer = read_val::read_line()?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// How many md5 or id mismatches `validate` found, for the exit code.
+pub fn mismatches() -> usize {
+    MISMATCHES.load(Ordering::Relaxed)
+}
+
 pub fn validate(args: &Args) -> Result<(), Box<dyn Error>> {
     // Read and validate JSON entries
     let mut file = File::open(args.secret_mode_input_json.as_ref().unwrap().clone()).unwrap();
@@ -146,40 +318,27 @@ pub fn validate(args: &Args) -> Result<(), Box<dyn Error>> {
     file.read_to_string(&mut file_content).unwrap();
     let json_values: Vec<Value> = serde_json::from_str(&file_content).unwrap();
 
-    let mut client_id = args.secrets_client_id.as_ref().unwrap();
-    let client_id_content;
-    if client_id.contains(path::MAIN_SEPARATOR) {
-        client_id_content = get_content_from_file(client_id);
-        client_id = &client_id_content;
-    }
-    let client_secret = args.secrets_client_secret_path.as_ref().unwrap();
-    let mut tenant_id = args.secrets_tenant_id.as_ref().unwrap();
-    let tenant_id_content;
-    if tenant_id.contains(path::MAIN_SEPARATOR) {
-        tenant_id_content = get_content_from_file(tenant_id);
-        tenant_id = &tenant_id_content;
-    }
-    let mut kev_vault_name = args.secrets_vault_name.as_ref().unwrap();
-    let kev_vault_name_content;
-    if kev_vault_name.contains(path::MAIN_SEPARATOR) {
-        kev_vault_name_content = get_content_from_file(kev_vault_name);
-        kev_vault_name = &kev_vault_name_content;
-    }
-
-    let client = get_keyvault_secret_client(client_id, client_secret, tenant_id, kev_vault_name);
+    let client = keyvault_client_from_args(args);
     let mut json_outputs: Vec<JsonOutput> = vec![];
 
+    // with concurrency > 1, download everything up front so the prompts below don't wait on the vault
+    let mut prefetched = if args.download_concurrency > 1 {
+        prefetch_secrets(&json_values, &client, args.download_concurrency as usize)
+    } else {
+        HashMap::new()
+    };
+
     let mut loop_result: JsonOutputControl = JsonOutputControl::new();
     for entry in json_values {
         // let string_representation = serde_json::to_string(&entry).unwrap();
         // dbg!(&string_representation);
 
         if loop_result.validate_all {
-            let z = validate_entry(entry, &client, args).unwrap();
+            let z = validate_entry(entry, &client, args, &mut prefetched)?;
             json_outputs.push(z);
             continue;
         } else {
-            match read_val_loop(entry, &client, args) {
+            match read_val_loop(entry, &client, args, &mut prefetched) {
                 Ok(result) => {
                     json_outputs.push(result.jsonoutput);
                     loop_result.validate_all = result.validate_all;
@@ -209,6 +368,7 @@ fn read_val_loop(
     entry: Value,
     client: &SecretClient,
     args: &Args,
+    prefetched: &mut HashMap<String, SetSecretResponse>,
 ) -> Result<JsonOutputControl, Box<dyn Error>> {
     let mut grammars: Vec<Grammar> = vec![];
     let mut tt: JsonOutputControl = JsonOutputControl {
@@ -221,6 +381,11 @@ fn read_val_loop(
             az_updated: String::new(),
             az_name: String::new(),
             hostname: String::new(),
+            encryption: None,
+            nonce: None,
+            signature: None,
+            signer: None,
+            host_overrides: None,
         },
         validate_all: false,
     };
@@ -236,8 +401,7 @@ fn read_val_loop(
     };
     grammars.push(static_prompt_grammar);
 
-    let file_name = entry["filenm"]
-        .as_str()
+    let file_name = host::local_filenm(&entry)
         .ok_or("filenm missing in input json")
         .unwrap();
 
@@ -275,7 +439,7 @@ fn read_val_loop(
     // let mut validate_all = false;
     loop {
         if tt.validate_all {
-            let z = validate_entry(entry, client, args).unwrap();
+            let z = validate_entry(entry, client, args, prefetched)?;
             tt.jsonoutput = z;
             break;
         } else {
@@ -294,7 +458,7 @@ fn read_val_loop(
                         details_about_entry(&entry);
                     }
                     "v" => {
-                        let z = validate_entry(entry, client, args).unwrap();
+                        let z = validate_entry(entry, client, args, prefetched)?;
                         tt.jsonoutput = z;
                         break;
                     }
@@ -321,8 +485,7 @@ fn read_val_loop(
 }
 
 fn details_about_entry(entry: &Value) {
-    let filenm = entry["filenm"]
-        .as_str()
+    let filenm = host::local_filenm(entry)
         .ok_or("filenm missing in input json")
         .unwrap();
     let az_name = entry["az_name"]
@@ -339,6 +502,13 @@ fn details_about_entry(entry: &Value) {
         .unwrap();
 
     println!("File: {}", filenm);
+    if let Some(shared) = entry["filenm"].as_str().filter(|f| *f != filenm) {
+        println!(
+            "  (host override for {}, filenm is {})",
+            host::this_host(),
+            shared
+        );
+    }
     println!("Azure Key Vault Name: {}", az_name);
 
     let x = vec![
@@ -366,6 +536,7 @@ fn validate_entry(
     entry: Value,
     client: &SecretClient,
     args: &Args,
+    prefetched: &mut HashMap<String, SetSecretResponse>,
 ) -> Result<JsonOutput, Box<dyn Error>> {
     let mut output = JsonOutput {
         filenm: String::new(),
@@ -376,13 +547,17 @@ fn validate_entry(
         az_updated: String::new(),
         az_name: String::new(),
         hostname: String::new(),
+        encryption: None,
+        nonce: None,
+        signature: None,
+        signer: None,
+        host_overrides: None,
     };
     let mut az_id = entry["az_id"]
         .as_str()
         .ok_or("az_id missing in input json")
         .unwrap();
-    let filenm = entry["filenm"]
-        .as_str()
+    let filenm = host::local_filenm(&entry)
         .ok_or("filenm missing in input json")
         .unwrap();
     let mut az_name = entry["az_name"]
@@ -392,8 +567,57 @@ fn validate_entry(
     let az_create; // = entry["az_create"].as_str().ok_or("az_create missing in input json").unwrap();
     let az_updated; // = entry["az_updated"].as_str().ok_or("az_updated missing in input json").unwrap();
 
-    let rt = Runtime::new().unwrap();
-    let secret_value = rt.block_on(get_secret_value(az_name, &client)).unwrap();
+    let mut secret_value = match prefetched.remove(az_name) {
+        Some(secret_value) => secret_value,
+        None => {
+            let rt = Runtime::new().unwrap();
+            rt.block_on(get_secret_value(az_name, &client)).unwrap()
+        }
+    };
+    if let Some(key_file) = &args.verify_key_file {
+        let key = match signing::read_verifying_key(key_file) {
+            Ok(key) => key,
+            Err(e) => {
+                MISMATCHES.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Signature check failed for {} ({}): {}", filenm, az_name, e);
+                return Ok(output);
+            }
+        };
+        let checked = match entry["signature"].as_str() {
+            Some(signature) => signing::verify(&key, az_name, &secret_value.value, signature),
+            None => Err("it has no signature in the input json".to_string()),
+        };
+        if let Err(e) = checked {
+            // nothing else about a secret that fails its check can be trusted
+            MISMATCHES.fetch_add(1, Ordering::Relaxed);
+            eprintln!(
+                "Signature check failed for {} ({}): {}. It was changed since it was signed, or signed with another key.",
+                filenm, az_name, e
+            );
+            return Ok(output);
+        }
+        if args.verbose {
+            println!("Signature match for file: {}", filenm);
+        }
+    }
+    if let Some(algorithm) = entry["encryption"].as_str() {
+        if algorithm != encryption::ALGORITHM {
+            MISMATCHES.fetch_add(1, Ordering::Relaxed);
+            eprintln!(
+                "{} ({}) is encrypted with unsupported {}",
+                filenm, az_name, algorithm
+            );
+            return Ok(output);
+        }
+        match decrypt_entry(&entry, args, &secret_value.value) {
+            Ok(value) => secret_value.value = value,
+            Err(e) => {
+                MISMATCHES.fetch_add(1, Ordering::Relaxed);
+                eprintln!("Decryption failed for {} ({}): {}", filenm, az_name, e);
+                return Ok(output);
+            }
+        }
+    }
 
     az_name = &secret_value.name;
     az_create = secret_value.created.to_string();
@@ -408,11 +632,13 @@ fn validate_entry(
         }
     };
     if md5 != md5_of_file {
+        MISMATCHES.fetch_add(1, Ordering::Relaxed);
         eprintln!("MD5 mismatch for file: {}", filenm);
     } else if args.verbose {
         println!("MD5 match for file: {}", filenm);
     }
     if az_id != secret_value.id {
+        MISMATCHES.fetch_add(1, Ordering::Relaxed);
         eprintln!(
             "Azure ID mismatch: id from azure {}, id from file {}",
             secret_value.id, az_id
@@ -431,22 +657,39 @@ fn validate_entry(
     let datetime_local: DateTime<Local> = datetime_utc.with_timezone(&Local);
     let formatted_date = datetime_local.to_rfc3339();
 
-    let hostname = hostname::get().unwrap().into_string().unwrap();
-
     output = JsonOutput {
-        filenm: filenm.to_string(),
+        // the entry's own path, so the output can be shared like the input; filenm is where it is here
+        filenm: entry["filenm"].as_str().unwrap_or(filenm).to_string(),
         md5: md5,
         ins_ts: formatted_date,
         az_id: az_id.to_string(),
         az_create: az_create,
         az_updated: az_updated,
         az_name: az_name.to_string(),
-        hostname: hostname,
+        hostname: host::this_host().to_string(),
+        encryption: entry["encryption"].as_str().map(|s| s.to_string()),
+        nonce: entry["nonce"].as_str().map(|s| s.to_string()),
+        signature: entry["signature"].as_str().map(|s| s.to_string()),
+        signer: entry["signer"].as_str().map(|s| s.to_string()),
+        host_overrides: entry.get("host_overrides").cloned(),
     };
 
     Ok(output)
 }
 
+/// The plaintext of an encrypted secret, using the entry's nonce and --encryption-key-file.
+fn decrypt_entry(entry: &Value, args: &Args, value: &str) -> Result<String, Box<dyn Error>> {
+    let key_file = args
+        .encryption_key_file
+        .as_ref()
+        .ok_or("it is encrypted, pass --encryption-key-file")?;
+    let nonce = entry["nonce"]
+        .as_str()
+        .ok_or("nonce missing in input json")?;
+    let cipher = encryption::read_key(key_file)?;
+    Ok(encryption::decrypt(&cipher, value, nonce)?)
+}
+
 fn write_json_output(input: &Vec<JsonOutput>, output_file: &str) {
     let mut file = fs::OpenOptions::new()
         .create(true)
@@ -459,6 +702,53 @@ fn write_json_output(input: &Vec<JsonOutput>, output_file: &str) {
     file.write_all(json.as_bytes()).unwrap();
 }
 
+/// Download the secrets named in the input json, at most `concurrency` at a time.
+/// Secrets that fail to download are left out, so they get fetched (and reported) again when validated.
+fn prefetch_secrets(
+    json_values: &[Value],
+    client: &SecretClient,
+    concurrency: usize,
+) -> HashMap<String, SetSecretResponse> {
+    let names: Vec<String> = json_values
+        .iter()
+        .filter_map(|entry| entry["az_name"].as_str().map(|s| s.to_string()))
+        .collect();
+
+    let rt = Runtime::new().unwrap();
+    rt.block_on(async {
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let client = Arc::new(client.clone());
+        let mut tasks = JoinSet::new();
+        for name in names {
+            let semaphore = Arc::clone(&semaphore);
+            let client = Arc::clone(&client);
+            tasks.spawn(async move {
+                let _permit = semaphore.acquire_owned().await.unwrap();
+                let result = get_secret_value(&name, &client)
+                    .await
+                    .map_err(|e| e.to_string());
+                (name, result)
+            });
+        }
+
+        let mut prefetched = HashMap::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok((name, Ok(secret_value))) => {
+                    prefetched.insert(name, secret_value);
+                }
+                Ok((name, Err(e))) => {
+                    eprintln!("Error downloading secret {}: {}", name, e);
+                }
+                Err(e) => {
+                    eprintln!("Error downloading secret: {}", e);
+                }
+            }
+        }
+        prefetched
+    })
+}
+
 async fn get_secret_value(
     secret_name: &str,
     kv_client: &SecretClient,
@@ -485,7 +775,10 @@ async fn set_secret_value(
     kv_client: &SecretClient,
     secret_value: &str,
 ) -> Result<SetSecretResponse, Box<dyn Error>> {
-    kv_client.set(secret_name, secret_value).await.unwrap();
+    kv_client
+        .set(secret_name, secret_value)
+        .await
+        .map_err(|e| e.to_string())?;
     Ok(get_secret_value(secret_name, kv_client).await.unwrap())
 }
 
@@ -501,6 +794,18 @@ fn get_keyvault_secret_client(
     tenant_id: &str,
     kev_vault_name: &str,
 ) -> SecretClient {
+    let credential = get_credential(client_id, client_secret, tenant_id);
+    let client = KeyvaultClient::new(kev_vault_name, credential)
+        .unwrap()
+        .secret_client();
+    client
+}
+
+fn get_credential(
+    client_id: &str,
+    client_secret: &PathBuf,
+    tenant_id: &str,
+) -> Arc<ClientSecretCredential> {
     let mut secret = String::new();
     let mut file = File::open(client_secret).unwrap();
     file.read_to_string(&mut secret).unwrap();
@@ -509,17 +814,376 @@ fn get_keyvault_secret_client(
 
     let http_client = Arc::new(Client::new());
     let authority_host = Url::parse("https://login.microsoftonline.com/").unwrap();
-    let credential = Arc::new(ClientSecretCredential::new(
+    Arc::new(ClientSecretCredential::new(
         http_client,
         authority_host,
         tenant_id.to_string(),
         client_id.to_string(),
         secret.to_string(),
-    ));
-    let client = KeyvaultClient::new(kev_vault_name, credential)
-        .unwrap()
-        .secret_client();
-    client
+    ))
+}
+
+/// Key Vault's deleted-secrets api, which the SecretClient doesn't cover.
+struct DeletedSecrets {
+    credential: Arc<ClientSecretCredential>,
+    http_client: Client,
+    vault_url: String,
+}
+
+impl DeletedSecrets {
+    fn new(
+        client_id: &str,
+        client_secret: &PathBuf,
+        tenant_id: &str,
+        kev_vault_name: &str,
+    ) -> Self {
+        DeletedSecrets {
+            credential: get_credential(client_id, client_secret, tenant_id),
+            http_client: Client::new(),
+            vault_url: format!("https://{}.vault.azure.net", kev_vault_name),
+        }
+    }
+
+    /// Undo the soft delete, bringing back the secret with all its versions.
+    async fn recover(&self, secret_name: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/deletedsecrets/{}/recover?api-version=7.4",
+            self.vault_url, secret_name
+        );
+        self.send(self.http_client.post(url)).await
+    }
+
+    /// Permanently delete the soft-deleted secret, freeing its name.
+    async fn purge(&self, secret_name: &str) -> Result<(), String> {
+        let url = format!(
+            "{}/deletedsecrets/{}?api-version=7.4",
+            self.vault_url, secret_name
+        );
+        self.send(self.http_client.delete(url)).await
+    }
+
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<(), String> {
+        let token = self
+            .credential
+            .get_token(&["https://vault.azure.net/.default"])
+            .await
+            .map_err(|e| e.to_string())?;
+        let response = request
+            .bearer_auth(token.token.secret())
+            .header("Content-Length", "0")
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if body.contains("purge protection") {
+            Err("the vault has purge protection enabled, so the name stays taken until the retention period ends. Recover it instead.".to_string())
+        } else {
+            Err(format!("{}: {}", status, body))
+        }
+    }
+}
+
+/// A set on a name that's soft-deleted fails with a conflict until the secret is recovered or purged.
+fn is_soft_deleted_conflict(msg: &str) -> bool {
+    msg.contains("ObjectIsDeletedButRecoverable") || msg.contains("deleted but recoverable")
+}
+
+/// Ask whether to recover or purge the soft-deleted `secret_name` so it can be set again. Returns whether to retry the set.
+fn resolve_soft_deleted(
+    secret_name: &str,
+    deleted_secrets: &DeletedSecrets,
+    rt: &Runtime,
+) -> Result<bool, Box<dyn Error>> {
+    println!(
+        "Secret {} was deleted but is still recoverable in the vault, so its name can't be reused yet.",
+        secret_name
+    );
+    loop {
+        print!("Recover it (r), purge it for good (p), or skip this file (s)? [r/p/s]: ");
+        let input = read_val::read_line()?;
+        // nothing at all, not even a newline, means stdin is closed; nobody is left to answer
+        if input.is_empty() {
+            println!();
+            return Ok(false);
+        }
+        let result = match input.trim() {
+            "r" => rt.block_on(deleted_secrets.recover(secret_name)),
+            "p" => rt.block_on(deleted_secrets.purge(secret_name)),
+            "s" => return Ok(false),
+            _ => continue,
+        };
+        match result {
+            Ok(()) => return Ok(true),
+            Err(e) => eprintln!("Error: {}", e),
+        }
+    }
+}
+
+/// Set a secret, waiting out the conflicts while a recover or purge is still finishing on the vault's side.
+async fn set_secret_value_with_retry(
+    secret_name: &str,
+    kv_client: &SecretClient,
+    secret_value: &str,
+) -> Result<SetSecretResponse, Box<dyn Error>> {
+    let mut attempts = 0;
+    loop {
+        match set_secret_value(secret_name, kv_client, secret_value).await {
+            Err(e) if attempts < 10 && e.to_string().contains("Conflict") => {
+                attempts += 1;
+                tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Client for the vault in args, where client id, tenant id and vault name may each be passed as a value or a file holding it.
+fn keyvault_client_from_args(args: &Args) -> SecretClient {
+    let mut client_id = args.secrets_client_id.as_ref().unwrap();
+    let client_id_content;
+    if client_id.contains(path::MAIN_SEPARATOR) {
+        client_id_content = get_content_from_file(client_id);
+        client_id = &client_id_content;
+    }
+    let client_secret = args.secrets_client_secret_path.as_ref().unwrap();
+    let mut tenant_id = args.secrets_tenant_id.as_ref().unwrap();
+    let tenant_id_content;
+    if tenant_id.contains(path::MAIN_SEPARATOR) {
+        tenant_id_content = get_content_from_file(tenant_id);
+        tenant_id = &tenant_id_content;
+    }
+    let mut kev_vault_name = args.secrets_vault_name.as_ref().unwrap();
+    let kev_vault_name_content;
+    if kev_vault_name.contains(path::MAIN_SEPARATOR) {
+        kev_vault_name_content = get_content_from_file(kev_vault_name);
+        kev_vault_name = &kev_vault_name_content;
+    }
+
+    get_keyvault_secret_client(client_id, client_secret, tenant_id, kev_vault_name)
+}
+
+/// List the secrets in the vault that no entry in the input json refers to. With --secret-gc-prefix, only
+/// those under that prefix, and offer to delete them; the vault may hold secrets this tool didn't upload.
+pub fn gc(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut file = File::open(args.secret_mode_input_json.as_ref().unwrap())?;
+    let mut file_content = String::new();
+    file.read_to_string(&mut file_content)?;
+    let json_values: Vec<Value> = serde_json::from_str(&file_content)?;
+    let referenced: HashSet<&str> = json_values
+        .iter()
+        .filter_map(|entry| entry["az_name"].as_str())
+        .collect();
+
+    let client = keyvault_client_from_args(args);
+    let rt = Runtime::new().unwrap();
+    let vault_secrets = rt.block_on(list_secret_names(&client))?;
+    let prefix = args.secret_gc_prefix.as_deref();
+    let candidates: Vec<&String> = vault_secrets
+        .iter()
+        .filter(|name| prefix.is_none_or(|prefix| name.starts_with(prefix)))
+        .collect();
+    let orphans: Vec<&String> = candidates
+        .iter()
+        .copied()
+        .filter(|name| !referenced.contains(name.as_str()))
+        .collect();
+
+    let scope = match prefix {
+        Some(prefix) => format!("secrets named {}* in the vault", prefix),
+        None => "secrets in the vault".to_string(),
+    };
+    if orphans.is_empty() {
+        println!(
+            "All {} {} are referenced by the input json.",
+            candidates.len(),
+            scope
+        );
+        return Ok(());
+    }
+    println!(
+        "{} of {} {} aren't referenced by the input json:",
+        orphans.len(),
+        candidates.len(),
+        scope
+    );
+    for name in &orphans {
+        println!("  {}", name);
+    }
+
+    if prefix.is_none() {
+        println!(
+            "The vault may hold secrets other tools use, pass --secret-gc-prefix to delete the orphans under a prefix."
+        );
+        return Ok(());
+    }
+    print!("Delete these {} secrets? [y/N]: ", orphans.len());
+    let input = read_val::read_line()?;
+    if input.trim() != "y" {
+        return Ok(());
+    }
+    for name in orphans {
+        match rt.block_on(client.delete(name.as_str()).into_future()) {
+            Ok(_) => {
+                audit::record(args, "secret-delete", name, "ok");
+                println!("Deleted {}", name);
+            }
+            Err(e) => {
+                audit::record(args, "secret-delete", name, &e.to_string());
+                eprintln!("Error deleting {}: {}", name, e);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Names of all secrets in the vault. The list api returns ids like https://<vault>.vault.azure.net/secrets/<name>.
+async fn list_secret_names(client: &SecretClient) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names = vec![];
+    let mut pages = client.list_secrets().into_stream();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(|e| e.to_string())?;
+        for secret in page.value {
+            if let Some(name) = secret.id.trim_end_matches('/').rsplit('/').next() {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
+
+/// Check each piece of the Key Vault credentials on its own and report which one is broken,
+/// rather than failing somewhere in the middle of a refresh or retrieve. Returns whether all checks passed.
+pub fn check_auth(args: &Args) -> Result<bool, Box<dyn Error>> {
+    let mut all_ok = true;
+    let mut report = |check: &str, result: Result<String, String>| {
+        match &result {
+            Ok(detail) => println!("[ ok ] {:<14} {}", check, detail),
+            Err(detail) => println!("[FAIL] {:<14} {}", check, detail),
+        }
+        if result.is_err() {
+            all_ok = false;
+        }
+        result.ok()
+    };
+
+    let client_id = report(
+        "client id",
+        read_guid_arg(args.secrets_client_id.as_deref(), "--secrets-client-id"),
+    );
+    let tenant_id = report(
+        "tenant id",
+        read_guid_arg(args.secrets_tenant_id.as_deref(), "--secrets-tenant-id"),
+    );
+    let vault_name = report(
+        "vault name",
+        check_vault_name(args.secrets_vault_name.as_deref()),
+    );
+    let client_secret = report(
+        "client secret",
+        check_client_secret(args.secrets_client_secret_path.as_ref()),
+    );
+
+    if let (Some(client_id), Some(tenant_id), Some(vault_name), Some(_)) =
+        (client_id, tenant_id, vault_name, client_secret)
+    {
+        let client = get_keyvault_secret_client(
+            &client_id,
+            args.secrets_client_secret_path.as_ref().unwrap(),
+            &tenant_id,
+            &vault_name,
+        );
+        let rt = Runtime::new().unwrap();
+        let result = rt.block_on(client.get(CANARY_SECRET_NAME).into_future());
+        report(
+            "vault access",
+            match result {
+                Ok(_) => Ok(format!("read secret {}", CANARY_SECRET_NAME)),
+                Err(e) => classify_keyvault_error(&e.to_string()),
+            },
+        );
+    } else {
+        println!("[skip] {:<14} fix the checks above first", "vault access");
+    }
+
+    Ok(all_ok)
+}
+
+/// Looked up by check-auth. It doesn't need to exist, a not-found answer proves the credentials work.
+const CANARY_SECRET_NAME: &str = "podman-compose-mgr-canary";
+
+fn read_guid_arg(value: Option<&str>, arg_name: &str) -> Result<String, String> {
+    let value = value.ok_or(format!("{} not passed", arg_name))?;
+    let guid = if value.contains(path::MAIN_SEPARATOR) {
+        fs::read_to_string(value)
+            .map_err(|e| format!("can't read {}: {}", value, e))?
+            .trim()
+            .to_string()
+    } else {
+        value.to_string()
+    };
+    let re = Regex::new(r"^[0-9a-fA-F]{8}-([0-9a-fA-F]{4}-){3}[0-9a-fA-F]{12}$").unwrap();
+    if re.is_match(&guid) {
+        Ok(guid)
+    } else {
+        Err(format!("'{}' is not a guid", guid))
+    }
+}
+
+fn check_vault_name(value: Option<&str>) -> Result<String, String> {
+    let value = value.ok_or("--secrets-vault-name not passed".to_string())?;
+    let name = if value.contains(path::MAIN_SEPARATOR) {
+        fs::read_to_string(value)
+            .map_err(|e| format!("can't read {}: {}", value, e))?
+            .trim()
+            .to_string()
+    } else {
+        value.to_string()
+    };
+    let host = format!("{}.vault.azure.net:443", name);
+    match std::net::ToSocketAddrs::to_socket_addrs(&host) {
+        Ok(_) => Ok(name),
+        Err(e) => Err(format!("can't resolve {}: {}", host, e)),
+    }
+}
+
+fn check_client_secret(value: Option<&PathBuf>) -> Result<String, String> {
+    let value = value.ok_or("--secrets-client-secret-path not passed".to_string())?;
+    let secret =
+        fs::read_to_string(value).map_err(|e| format!("can't read {}: {}", value.display(), e))?;
+    if secret.trim().is_empty() {
+        Err(format!("{} is empty", value.display()))
+    } else {
+        Ok(format!("read from {}", value.display()))
+    }
+}
+
+/// Map the error from a Key Vault read onto the credential or permission that's at fault.
+fn classify_keyvault_error(msg: &str) -> Result<String, String> {
+    if msg.contains("SecretNotFound") || msg.contains("404") {
+        Ok(format!(
+            "authenticated, {} doesn't exist (that's fine)",
+            CANARY_SECRET_NAME
+        ))
+    } else if msg.contains("AADSTS7000215") || msg.contains("AADSTS7000222") {
+        Err("client secret is invalid or expired".to_string())
+    } else if msg.contains("AADSTS700016") {
+        Err("client id is not an app registered in this tenant".to_string())
+    } else if msg.contains("AADSTS90002") || msg.contains("AADSTS900023") {
+        Err("tenant id not found".to_string())
+    } else if msg.contains("403") || msg.contains("Forbidden") {
+        Err(format!(
+            "authenticated, but not allowed to get secrets in this vault: {}",
+            msg
+        ))
+    } else if msg.contains("401") || msg.contains("Unauthorized") {
+        Err(format!("vault rejected the credentials: {}", msg))
+    } else {
+        Err(msg.to_string())
+    }
 }
 
 fn get_content_from_file(file_path: &str) -> String {
@@ -0,0 +1,101 @@
+use crate::read_val;
+use crate::state::StateDir;
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{self, PathBuf};
+
+const SESSION_FILE: &str = "session.json";
+
+#[derive(Serialize, Deserialize)]
+struct Saved {
+    /// RFC 3339
+    started: String,
+    roots: Vec<PathBuf>,
+    /// `image container` for each service reviewed, just `image` for a build file's
+    done: Vec<String>,
+}
+
+/// What an interactive rebuild has been through so far, saved in the state dir after each image
+/// and removed once the scan finishes. If a run is quit partway, the next one over the same roots
+/// offers to skip what it already got through.
+pub struct Session {
+    file: PathBuf,
+    saved: Saved,
+    resumed: HashSet<String>,
+}
+
+impl Session {
+    pub fn start(state: &StateDir, roots: &[PathBuf]) -> Result<Session, String> {
+        let file = state.path().join("history").join(SESSION_FILE);
+        let roots: Vec<PathBuf> = roots
+            .iter()
+            .map(|root| path::absolute(root).unwrap_or_else(|_| root.clone()))
+            .collect();
+        let mut session = Session {
+            file,
+            saved: Saved {
+                started: Local::now().to_rfc3339(),
+                roots,
+                done: vec![],
+            },
+            resumed: HashSet::new(),
+        };
+        if let Some(last) = session.unfinished()? {
+            let started = DateTime::parse_from_rfc3339(&last.started)
+                .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|_| last.started.clone());
+            print!(
+                "The rebuild started {} stopped after {} image(s). Resume, skipping those? Y/n: ",
+                started,
+                last.done.len()
+            );
+            let answer = read_val::read_line().unwrap_or_default();
+            if !answer.trim().eq_ignore_ascii_case("n") {
+                session.resumed = last.done.iter().cloned().collect();
+                // the same session carries on, and is still resumable if this run is quit too
+                session.saved.started = last.started;
+                session.saved.done = last.done;
+            }
+        }
+        session.save()?;
+        Ok(session)
+    }
+
+    /// The last session over the same roots, when it got partway.
+    fn unfinished(&self) -> Result<Option<Saved>, String> {
+        if !self.file.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&self.file)
+            .map_err(|e| format!("Failed to read '{}': {}", self.file.display(), e))?;
+        let last: Saved = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse '{}': {}", self.file.display(), e))?;
+        Ok((last.roots == self.saved.roots && !last.done.is_empty()).then_some(last))
+    }
+
+    /// Whether the session this one resumed already went through `key`.
+    pub fn was_done(&self, key: &str) -> bool {
+        self.resumed.contains(key)
+    }
+
+    pub fn mark_done(&mut self, key: String) -> Result<(), String> {
+        self.saved.done.push(key);
+        self.save()
+    }
+
+    /// The scan got to the end, nothing to resume.
+    pub fn finish(self) -> Result<(), String> {
+        fs::remove_file(&self.file)
+            .map_err(|e| format!("Failed to remove '{}': {}", self.file.display(), e))
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.saved)
+            .map_err(|e| format!("Failed to serialize the session: {}", e))?;
+        fs::write(&self.file, json)
+            .map_err(|e| format!("Failed to write '{}': {}", self.file.display(), e))
+    }
+}
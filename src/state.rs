@@ -0,0 +1,120 @@
+use crate::args::Args;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bump this and add a migration below whenever the layout of the state dir changes.
+const STATE_VERSION: u32 = 2;
+const VERSION_FILE: &str = "version";
+
+type Migration = fn(&Path) -> Result<(), String>;
+
+/// Migrations indexed by the version they migrate *to*, run in order from the on-disk version.
+const MIGRATIONS: &[(u32, Migration)] = &[(1, migrate_to_v1), (2, migrate_to_v2)];
+
+/// All persisted state (history, caches, skip decisions, choices, locks) lives under one dir,
+/// `$XDG_STATE_HOME/podman-compose-mgr` by default.
+pub struct StateDir {
+    root: PathBuf,
+}
+
+impl StateDir {
+    /// Resolve the state dir, creating it or migrating it to the current version as needed.
+    pub fn open(args: &Args) -> Result<StateDir, String> {
+        let root = match &args.state_dir {
+            Some(dir) => dir.clone(),
+            None => default_state_dir()?,
+        };
+        fs::create_dir_all(&root)
+            .map_err(|e| format!("Failed to create state dir '{}': {}", root.display(), e))?;
+
+        let state = StateDir { root };
+        let on_disk = state.read_version()?;
+        if on_disk > STATE_VERSION {
+            return Err(format!(
+                "State dir '{}' is version {}, newer than this program understands ({}). Run `podman-compose-mgr state-clean` to reset it.",
+                state.root.display(),
+                on_disk,
+                STATE_VERSION
+            ));
+        }
+        if on_disk < STATE_VERSION {
+            for (to_version, migration) in MIGRATIONS.iter().filter(|(v, _)| *v > on_disk) {
+                if args.verbose {
+                    println!("Migrating state dir to version {}", to_version);
+                }
+                migration(&state.root)?;
+                state.write_version(*to_version)?;
+            }
+        }
+        Ok(state)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.root
+    }
+
+    /// Remove everything in the state dir.
+    pub fn clean(&self) -> Result<(), String> {
+        let entries = fs::read_dir(&self.root)
+            .map_err(|e| format!("Failed to read state dir '{}': {}", self.root.display(), e))?;
+        for entry in entries.filter_map(|e| e.ok()) {
+            let path = entry.path();
+            let result = if path.is_dir() {
+                fs::remove_dir_all(&path)
+            } else {
+                fs::remove_file(&path)
+            };
+            result.map_err(|e| format!("Failed to remove '{}': {}", path.display(), e))?;
+        }
+        Ok(())
+    }
+
+    fn read_version(&self) -> Result<u32, String> {
+        let version_file = self.root.join(VERSION_FILE);
+        if !version_file.exists() {
+            return Ok(0);
+        }
+        let content = fs::read_to_string(&version_file)
+            .map_err(|e| format!("Failed to read '{}': {}", version_file.display(), e))?;
+        content.trim().parse::<u32>().map_err(|e| {
+            format!(
+                "Invalid state version in '{}': {}",
+                version_file.display(),
+                e
+            )
+        })
+    }
+
+    fn write_version(&self, version: u32) -> Result<(), String> {
+        let version_file = self.root.join(VERSION_FILE);
+        fs::write(&version_file, format!("{}\n", version))
+            .map_err(|e| format!("Failed to write '{}': {}", version_file.display(), e))
+    }
+}
+
+fn default_state_dir() -> Result<PathBuf, String> {
+    if let Some(dir) = std::env::var_os("XDG_STATE_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("podman-compose-mgr"));
+    }
+    let home = std::env::var("HOME").map_err(|_| "HOME is not set".to_string())?;
+    Ok(PathBuf::from(home)
+        .join(".local")
+        .join("state")
+        .join("podman-compose-mgr"))
+}
+
+fn migrate_to_v1(root: &Path) -> Result<(), String> {
+    for subdir in ["history", "cache", "skip", "locks"] {
+        let dir = root.join(subdir);
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))?;
+    }
+    Ok(())
+}
+
+/// v2 remembers which build file to use in dirs with more than one.
+fn migrate_to_v2(root: &Path) -> Result<(), String> {
+    let dir = root.join("choices");
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create '{}': {}", dir.display(), e))
+}
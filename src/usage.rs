@@ -0,0 +1,96 @@
+use crate::helpers::image_name_helper_fns as image_name;
+
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+/// The compose and quadlet `.container` files using one tag of an image, and their dirs. `image` is
+/// the fully qualified name; `names` are the ways the files spell it.
+#[derive(Serialize)]
+pub struct TagUsage {
+    pub image: String,
+    pub names: Vec<String>,
+    pub files: Vec<PathBuf>,
+    pub dirs: Vec<PathBuf>,
+}
+
+/// Every tag of a repository in use. More than one is worth consolidating.
+#[derive(Serialize)]
+pub struct RepositoryUsage {
+    pub repository: String,
+    pub mismatched_tags: bool,
+    pub tags: Vec<TagUsage>,
+}
+
+/// Turn repocheck's image -> files index around into one entry per repository, sorted by name.
+/// Spellings of the same image are counted as one tag.
+pub fn report(image_users: &HashMap<String, Vec<PathBuf>>) -> Vec<RepositoryUsage> {
+    let mut by_image: BTreeMap<String, (Vec<String>, Vec<PathBuf>)> = BTreeMap::new();
+    for (name, files) in image_users {
+        let (names, all_files) = by_image
+            .entry(image_name::qualified_name(name))
+            .or_default();
+        names.push(name.clone());
+        all_files.extend(files.iter().cloned());
+    }
+    let mut by_repository: BTreeMap<String, Vec<TagUsage>> = BTreeMap::new();
+    for (image, (mut names, mut files)) in by_image {
+        names.sort();
+        files.sort();
+        files.dedup();
+        let mut dirs: Vec<PathBuf> = files
+            .iter()
+            .filter_map(|f| f.parent().map(|d| d.to_path_buf()))
+            .collect();
+        dirs.dedup();
+        by_repository
+            .entry(image_name::repository_of(&image).to_string())
+            .or_default()
+            .push(TagUsage {
+                image,
+                names,
+                files,
+                dirs,
+            });
+    }
+    by_repository
+        .into_iter()
+        .map(|(repository, tags)| RepositoryUsage {
+            repository,
+            mismatched_tags: tags.len() > 1,
+            tags,
+        })
+        .collect()
+}
+
+/// Each repository, then each tag of it and the files using that tag; or with --json, a JSON array.
+pub fn print(usages: &[RepositoryUsage], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(usages).unwrap());
+        return;
+    }
+    for usage in usages {
+        if usage.mismatched_tags {
+            println!("{} ({} different tags)", usage.repository, usage.tags.len());
+        } else {
+            println!("{}", usage.repository);
+        }
+        for tag in &usage.tags {
+            let mut label = tag.image.strip_prefix(&usage.repository).unwrap_or("");
+            if tag.names.iter().any(|n| *n != tag.image) {
+                println!("  {:<20}  as {}", label, tag.names.join(", "));
+                label = "";
+            }
+            for file in &tag.files {
+                println!("  {:<20}  {}", label, file.display());
+                label = "";
+            }
+        }
+    }
+    let mismatched = usages.iter().filter(|u| u.mismatched_tags).count();
+    println!(
+        "{} repositories, {} used under more than one tag.",
+        usages.len(),
+        mismatched
+    );
+}